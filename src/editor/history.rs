@@ -0,0 +1,121 @@
+use std::env;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+// Number of entries kept in memory and in the history file
+const MAX_ENTRIES: usize = 1000;
+
+const FILE_NAME: &str = ".rustl_history";
+
+// Lines entered in the REPL, oldest first. When a file is set, entries are loaded from it
+// and every new entry is appended to it.
+#[derive(Default)]
+pub struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History::default()
+    }
+
+    // History persisted to path, a missing or unreadable file starts an empty history
+    pub fn with_file(path: PathBuf) -> History {
+        let mut entries: Vec<String> = match fs::read_to_string(&path) {
+            Ok(text) => text.lines().filter(|l| !l.trim().is_empty()).map(String::from).collect(),
+            Err(_) => Vec::new(),
+        };
+        if entries.len() > MAX_ENTRIES {
+            entries.drain(..entries.len() - MAX_ENTRIES);
+            // rewrite the file so that it doesn't grow forever
+            let _ = fs::write(&path, entries.join("\n") + "\n");
+        }
+
+        History {
+            entries,
+            path: Some(path),
+        }
+    }
+
+    // History persisted to ~/.rustl_history, or only in memory if there is no home directory
+    pub fn from_home() -> History {
+        match env::var_os("HOME") {
+            Some(home) => History::with_file(PathBuf::from(home).join(FILE_NAME)),
+            None => History::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|s| s.as_ref())
+    }
+
+    // Adds a line, blank lines and repetitions of the last entry are ignored
+    pub fn add(&mut self, line: &str) {
+        let line = line.trim_end();
+        if line.trim().is_empty() || self.entries.last().map(|s| s.as_ref()) == Some(line) {
+            return;
+        }
+
+        self.entries.push(line.to_string());
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+
+        if let Some(ref path) = self.path {
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+    }
+
+    // Index of the most recent entry before the given index that contains query
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        let end = before.min(self.entries.len());
+        self.entries[..end].iter().rposition(|entry| entry.contains(query))
+    }
+}
+
+#[test]
+fn history_skips_blank_and_repeated_lines() {
+    let mut history = History::new();
+    history.add("(+ 1 2)");
+    history.add("(+ 1 2)\n");
+    history.add("   ");
+    history.add("(def! x 1)");
+
+    assert_eq!(history.entries, vec!["(+ 1 2)".to_string(), "(def! x 1)".to_string()]);
+}
+
+#[test]
+fn history_searches_backwards() {
+    let mut history = History::new();
+    history.add("(def! x 1)");
+    history.add("(+ x 2)");
+    history.add("(def! y 3)");
+
+    assert_eq!(history.search("def!", 3), Some(2));
+    assert_eq!(history.search("def!", 2), Some(0));
+    assert_eq!(history.search("def!", 0), None);
+    assert_eq!(history.search("z", 3), None);
+}
+
+#[test]
+fn history_is_persisted() {
+    let path = env::temp_dir().join(format!("rustl-history-{}", ::std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    let mut history = History::with_file(path.clone());
+    history.add("(+ 1 2)");
+    history.add("(def! x 1)");
+
+    let loaded = History::with_file(path.clone());
+    fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.entries, history.entries);
+}
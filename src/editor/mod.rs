@@ -0,0 +1,623 @@
+// A small line editor for the REPL: cursor movement, history with reverse search,
+// highlighting of matching parens and completion of the names bound in the environment.
+// The terminal is put in raw mode while a line is edited, see terminal.rs.
+
+pub mod history;
+pub mod terminal;
+
+use std::io;
+use std::io::{Read, Write};
+
+use lisp::lisp::eval::KEYWORDS;
+
+use self::history::History;
+use self::terminal::RawMode;
+
+const BELL: &str = "\x07";
+const HIGHLIGHT: &str = "\x1b[7m";
+const RESET: &str = "\x1b[0m";
+
+// A key press decoded from the bytes sent by the terminal
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Key {
+    Char(char),
+    Ctrl(char),
+    Enter,
+    Backspace,
+    Delete,
+    Tab,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    // sequences the editor doesn't handle
+    Unknown,
+}
+
+// The outcome of editing a line
+#[derive(Debug, PartialEq)]
+pub enum ReadLine {
+    Line(String),
+    // ^D on an empty line, or the end of the input
+    Eof,
+    // ^C, the line is discarded
+    Interrupted,
+}
+
+fn read_byte<R: Read>(input: &mut R) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    loop {
+        match input.read(&mut byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(byte[0])),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Reads the next key, returns None at the end of the input
+pub fn read_key<R: Read>(input: &mut R) -> io::Result<Option<Key>> {
+    let byte = match read_byte(input)? {
+        Some(byte) => byte,
+        None => return Ok(None),
+    };
+
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        127 | 8 => Key::Backspace,
+        b'\t' => Key::Tab,
+        27 => read_escape(input)?,
+        1..=26 => Key::Ctrl((b'a' + byte - 1) as char),
+        0..=31 => Key::Unknown,
+        0x80..=0xff => read_utf8(byte, input)?,
+        _ => Key::Char(byte as char),
+    };
+    Ok(Some(key))
+}
+
+// Decodes the escape sequences sent for arrows, Home, End and Delete
+fn read_escape<R: Read>(input: &mut R) -> io::Result<Key> {
+    match read_byte(input)? {
+        Some(b'[') | Some(b'O') => (),
+        _ => return Ok(Key::Unknown),
+    }
+
+    let key = match read_byte(input)? {
+        Some(b'A') => Key::Up,
+        Some(b'B') => Key::Down,
+        Some(b'C') => Key::Right,
+        Some(b'D') => Key::Left,
+        Some(b'H') => Key::Home,
+        Some(b'F') => Key::End,
+        Some(digit @ b'0'..=b'9') => {
+            // sequences such as ESC [ 3 ~, parameters after ; are ignored
+            let mut number = vec![digit];
+            loop {
+                match read_byte(input)? {
+                    Some(b'~') | None => break,
+                    Some(byte) => number.push(byte),
+                }
+            }
+            match &number[..] {
+                b"1" | b"7" => Key::Home,
+                b"4" | b"8" => Key::End,
+                b"3" => Key::Delete,
+                _ => Key::Unknown,
+            }
+        },
+        _ => Key::Unknown,
+    };
+    Ok(key)
+}
+
+// Decodes a character encoded on several bytes, starting with first
+fn read_utf8<R: Read>(first: u8, input: &mut R) -> io::Result<Key> {
+    let length = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return Ok(Key::Unknown),
+    };
+
+    let mut bytes = vec![first];
+    for _ in 1..length {
+        match read_byte(input)? {
+            Some(byte) => bytes.push(byte),
+            None => return Ok(Key::Unknown),
+        }
+    }
+    Ok(match ::std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+        Some(c) => Key::Char(c),
+        None => Key::Unknown,
+    })
+}
+
+// The text of the line being edited and the position of the cursor, in chars
+#[derive(Default)]
+pub struct LineBuffer {
+    chars: Vec<char>,
+    cursor: usize,
+}
+
+impl LineBuffer {
+    pub fn new() -> LineBuffer {
+        LineBuffer::default()
+    }
+
+    pub fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    // Replaces the text, with the cursor at the end
+    pub fn set(&mut self, text: &str) {
+        self.chars = text.chars().collect();
+        self.cursor = self.chars.len();
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.chars.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        for c in text.chars() {
+            self.insert(c);
+        }
+    }
+
+    // Deletes the char before the cursor, returns false if there is none
+    pub fn backspace(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        self.cursor -= 1;
+        self.chars.remove(self.cursor);
+        true
+    }
+
+    // Deletes the char under the cursor, returns false if there is none
+    pub fn delete(&mut self) -> bool {
+        if self.cursor == self.chars.len() {
+            return false;
+        }
+        self.chars.remove(self.cursor);
+        true
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.chars.len());
+    }
+
+    pub fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.chars.len();
+    }
+
+    pub fn kill_to_end(&mut self) {
+        self.chars.truncate(self.cursor);
+    }
+
+    pub fn kill_to_start(&mut self) {
+        self.chars.drain(..self.cursor);
+        self.cursor = 0;
+    }
+
+    // Deletes the word before the cursor and the spaces after it
+    pub fn delete_word(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !self.chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        self.chars.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    // The start of the symbol ending at the cursor
+    fn symbol_start(&self) -> usize {
+        let mut start = self.cursor;
+        while start > 0 && !is_delimiter(self.chars[start - 1]) {
+            start -= 1;
+        }
+        start
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "()[]{}\"';".contains(c)
+}
+
+// The index of the paren, bracket or brace matching the one at index, those in strings and comments
+// are ignored
+pub fn matching_paren(chars: &[char], index: usize) -> Option<usize> {
+    let mut open = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, &c) in chars.iter().enumerate() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            ';' => break,
+            '(' | '[' | '{' => open.push(i),
+            ')' | ']' | '}' => {
+                if let Some(start) = open.pop() {
+                    if start == index {
+                        return Some(i);
+                    }
+                    if i == index {
+                        return Some(start);
+                    }
+                }
+            },
+            _ => (),
+        }
+    }
+    None
+}
+
+// The longest prefix shared by all the candidates
+fn common_prefix(candidates: &[String]) -> String {
+    let mut prefix = candidates.first().cloned().unwrap_or_default();
+    for candidate in candidates {
+        while !candidate.starts_with(&prefix) {
+            prefix.pop();
+        }
+    }
+    prefix
+}
+
+// Edits lines with the history shared between them
+pub struct Editor {
+    history: History,
+}
+
+impl Editor {
+    pub fn new(history: History) -> Editor {
+        Editor { history }
+    }
+
+    // Reads a line from the terminal, with stdin in raw mode until the line is complete.
+    // Fails if stdin is not a terminal.
+    pub fn read_line(&mut self, prompt: &str, names: &[String]) -> io::Result<ReadLine> {
+        let _raw = RawMode::enable()?;
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        self.edit(prompt, names, &mut stdin.lock(), &mut stdout.lock())
+    }
+
+    // Edits a line with the keys read from input, echoing it to out. names are offered for
+    // completion along with the special forms. Complete lines are added to the history.
+    pub fn edit<R: Read, W: Write>(&mut self, prompt: &str, names: &[String],
+                                   input: &mut R, out: &mut W) -> io::Result<ReadLine> {
+        let mut line = LineBuffer::new();
+        // the history entry being shown, history.len() is the line being edited
+        let mut index = self.history.len();
+        // the edited line, kept while browsing the history
+        let mut current = String::new();
+
+        render(prompt, &line, out)?;
+
+        let mut next = read_key(input)?;
+        while let Some(key) = next.take() {
+            match key {
+                Key::Enter => {
+                    let text = line.text();
+                    line.end();
+                    render_plain(prompt, &line, out)?;
+                    write!(out, "\r\n")?;
+                    out.flush()?;
+                    self.history.add(&text);
+                    return Ok(ReadLine::Line(text));
+                },
+                Key::Ctrl('c') => {
+                    write!(out, "^C\r\n")?;
+                    out.flush()?;
+                    return Ok(ReadLine::Interrupted);
+                },
+                Key::Ctrl('d') if line.is_empty() => {
+                    write!(out, "\r\n")?;
+                    out.flush()?;
+                    return Ok(ReadLine::Eof);
+                },
+                Key::Char(c) => line.insert(c),
+                Key::Backspace => ring_unless(line.backspace(), out)?,
+                Key::Delete | Key::Ctrl('d') => ring_unless(line.delete(), out)?,
+                Key::Left | Key::Ctrl('b') => line.left(),
+                Key::Right | Key::Ctrl('f') => line.right(),
+                Key::Home | Key::Ctrl('a') => line.home(),
+                Key::End | Key::Ctrl('e') => line.end(),
+                Key::Ctrl('k') => line.kill_to_end(),
+                Key::Ctrl('u') => line.kill_to_start(),
+                Key::Ctrl('w') => line.delete_word(),
+                Key::Ctrl('l') => write!(out, "\x1b[H\x1b[2J")?,
+                Key::Up | Key::Ctrl('p') => {
+                    if index == 0 {
+                        write!(out, "{}", BELL)?;
+                    } else {
+                        if index == self.history.len() {
+                            current = line.text();
+                        }
+                        index -= 1;
+                        line.set(self.history.get(index).unwrap_or(""));
+                    }
+                },
+                Key::Down | Key::Ctrl('n') => {
+                    if index >= self.history.len() {
+                        write!(out, "{}", BELL)?;
+                    } else {
+                        index += 1;
+                        match self.history.get(index) {
+                            Some(entry) => line.set(entry),
+                            None => line.set(&current),
+                        }
+                    }
+                },
+                Key::Tab => self.complete(prompt, names, &mut line, out)?,
+                Key::Ctrl('r') => {
+                    next = self.search(&mut line, input, out)?;
+                    render(prompt, &line, out)?;
+                    continue;
+                },
+                _ => (),
+            }
+
+            render(prompt, &line, out)?;
+            next = read_key(input)?;
+        }
+
+        // end of the input
+        write!(out, "\r\n")?;
+        out.flush()?;
+        Ok(ReadLine::Eof)
+    }
+
+    // Completes the symbol before the cursor. A single candidate is inserted, otherwise the
+    // common prefix of the candidates is inserted or the candidates are listed.
+    fn complete<W: Write>(&self, prompt: &str, names: &[String],
+                          line: &mut LineBuffer, out: &mut W) -> io::Result<()> {
+        let start = line.symbol_start();
+        let prefix: String = line.chars[start..line.cursor].iter().collect();
+
+        let mut candidates: Vec<String> = names.iter()
+            .map(|name| name.to_string())
+            .chain(KEYWORDS.iter().map(|name| name.to_string()))
+            .filter(|name| name.starts_with(&prefix))
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+
+        if prefix.is_empty() || candidates.is_empty() {
+            return write!(out, "{}", BELL);
+        }
+
+        let completion = common_prefix(&candidates);
+        if completion.len() > prefix.len() {
+            line.insert_str(&completion[prefix.len()..]);
+        } else if candidates.len() > 1 {
+            render_plain(prompt, line, out)?;
+            write!(out, "\r\n{}\r\n", candidates.join("  "))?;
+        }
+        Ok(())
+    }
+
+    // Searches the history for the lines containing the typed text, most recent first.
+    // ^R goes to the previous match, ^G restores the line, Enter and other keys accept the
+    // match and are then handled by edit, so they are returned. None is the end of the input.
+    fn search<R: Read, W: Write>(&self, line: &mut LineBuffer, input: &mut R,
+                                 out: &mut W) -> io::Result<Option<Key>> {
+        let original = line.text();
+        let mut query = String::new();
+        let mut found = None;
+        let mut failed = false;
+
+        loop {
+            let text = found.and_then(|i| self.history.get(i)).unwrap_or("");
+            write!(out, "\r{}reverse-i-search)`{}': {}\x1b[K",
+                   if failed { "(failed " } else { "(" }, query, text)?;
+            out.flush()?;
+
+            let key = match read_key(input)? {
+                Some(key) => key,
+                None => return Ok(None),
+            };
+
+            let before = match key {
+                Key::Char(c) => {
+                    query.push(c);
+                    // the current match may still contain the longer query
+                    found.map_or(self.history.len(), |i| i + 1)
+                },
+                Key::Backspace => {
+                    query.pop();
+                    self.history.len()
+                },
+                Key::Ctrl('r') => found.unwrap_or(self.history.len()),
+                Key::Ctrl('g') => {
+                    // nothing left for edit to do
+                    line.set(&original);
+                    return Ok(Some(Key::Unknown));
+                },
+                Key::Ctrl('c') => {
+                    line.set(&original);
+                    return Ok(Some(key));
+                },
+                _ => {
+                    if let Some(entry) = found.and_then(|i| self.history.get(i)) {
+                        line.set(entry);
+                    }
+                    return Ok(Some(key));
+                },
+            };
+
+            match self.history.search(&query, before) {
+                Some(i) => {
+                    found = Some(i);
+                    failed = false;
+                },
+                None => failed = !query.is_empty(),
+            }
+            if query.is_empty() {
+                found = None;
+            }
+        }
+    }
+}
+
+fn ring_unless<W: Write>(done: bool, out: &mut W) -> io::Result<()> {
+    if done {
+        Ok(())
+    } else {
+        write!(out, "{}", BELL)
+    }
+}
+
+// Draws the line with the paren matching the one at (or just before) the cursor highlighted
+fn render<W: Write>(prompt: &str, line: &LineBuffer, out: &mut W) -> io::Result<()> {
+    let paren = [line.cursor.wrapping_sub(1), line.cursor].iter()
+        .filter(|&&i| i < line.chars.len() && "()[]{}".contains(line.chars[i]))
+        .filter_map(|&i| matching_paren(&line.chars, i))
+        .next();
+
+    write!(out, "\r{}", prompt)?;
+    for (i, c) in line.chars.iter().enumerate() {
+        if Some(i) == paren {
+            write!(out, "{}{}{}", HIGHLIGHT, c, RESET)?;
+        } else {
+            write!(out, "{}", c)?;
+        }
+    }
+    finish_render(prompt, line, out)
+}
+
+// Draws the line without highlighting
+fn render_plain<W: Write>(prompt: &str, line: &LineBuffer, out: &mut W) -> io::Result<()> {
+    write!(out, "\r{}{}", prompt, line.text())?;
+    finish_render(prompt, line, out)
+}
+
+// Clears the rest of the terminal line and moves the cursor to its position in the line
+fn finish_render<W: Write>(prompt: &str, line: &LineBuffer, out: &mut W) -> io::Result<()> {
+    write!(out, "\x1b[K\r")?;
+    let column = prompt.chars().count() + line.cursor;
+    if column > 0 {
+        write!(out, "\x1b[{}C", column)?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+fn edit_keys(editor: &mut Editor, keys: &str) -> ReadLine {
+    let names = vec!["define-me".to_string(), "display".to_string(), "dot".to_string()];
+    editor.edit(">> ", &names, &mut io::Cursor::new(keys), &mut Vec::new()).unwrap()
+}
+
+#[test]
+fn editor_decodes_keys() {
+    let mut input = io::Cursor::new("a\x1b[A\x1b[D\x1bOH\x1b[3~\x1b[4~\x7f\x01\r\u{e9}\t");
+    let mut keys = Vec::new();
+    while let Some(key) = read_key(&mut input).unwrap() {
+        keys.push(key);
+    }
+
+    assert_eq!(keys, vec![Key::Char('a'), Key::Up, Key::Left, Key::Home, Key::Delete, Key::End,
+                          Key::Backspace, Key::Ctrl('a'), Key::Enter, Key::Char('\u{e9}'),
+                          Key::Tab]);
+}
+
+#[test]
+fn editor_edits_lines() {
+    let mut editor = Editor::new(History::new());
+
+    assert_eq!(edit_keys(&mut editor, "(+ 1 2)\r"), ReadLine::Line("(+ 1 2)".to_string()));
+    // move to the start, insert, delete under the cursor
+    assert_eq!(edit_keys(&mut editor, "+ 1 2\x01(\x05)\x1b[D\x1b[D\x1b[3~3\r"),
+               ReadLine::Line("(+ 1 3)".to_string()));
+    // kill the end, delete a word
+    assert_eq!(edit_keys(&mut editor, "(a b c)\x1b[D\x1b[D\x0b\x17x\r"),
+               ReadLine::Line("(a x".to_string()));
+    assert_eq!(edit_keys(&mut editor, "abc\x03"), ReadLine::Interrupted);
+    assert_eq!(edit_keys(&mut editor, "\x04"), ReadLine::Eof);
+    assert_eq!(edit_keys(&mut editor, "abc"), ReadLine::Eof);
+}
+
+#[test]
+fn editor_browses_history() {
+    let mut editor = Editor::new(History::new());
+    edit_keys(&mut editor, "(def! x 1)\r");
+    edit_keys(&mut editor, "(+ x 2)\r");
+    edit_keys(&mut editor, "(* x 3)\r");
+
+    assert_eq!(edit_keys(&mut editor, "\x1b[A\x1b[A\r"), ReadLine::Line("(+ x 2)".to_string()));
+    // coming back down restores the line being edited
+    assert_eq!(edit_keys(&mut editor, "x\x10\x0e\r"), ReadLine::Line("x".to_string()));
+    // reverse search, ^R again goes to the previous match
+    assert_eq!(edit_keys(&mut editor, "\x12x\x12\x12\r"), ReadLine::Line("(* x 3)".to_string()));
+    // a key other than Enter accepts the match and is applied to it
+    assert_eq!(edit_keys(&mut editor, "\x12def\x1b[D\x1b[D)\r"),
+               ReadLine::Line("(def! x )1)".to_string()));
+    assert_eq!(edit_keys(&mut editor, "y\x12def\x07\r"), ReadLine::Line("y".to_string()));
+}
+
+#[test]
+fn editor_completes_names() {
+    let mut editor = Editor::new(History::new());
+
+    assert_eq!(edit_keys(&mut editor, "(dis\t 1)\r"), ReadLine::Line("(display 1)".to_string()));
+    // the common prefix of several candidates, special forms are completed too
+    assert_eq!(edit_keys(&mut editor, "(de\t\r"), ReadLine::Line("(def".to_string()));
+    assert_eq!(edit_keys(&mut editor, "(lam\t\r"), ReadLine::Line("(lambda".to_string()));
+    assert_eq!(edit_keys(&mut editor, "(zz\t\r"), ReadLine::Line("(zz".to_string()));
+
+    let names = vec!["dot".to_string(), "display".to_string()];
+    let mut out = Vec::new();
+    editor.edit(">> ", &names, &mut io::Cursor::new("(d\t\r"), &mut out).unwrap();
+    assert!(String::from_utf8(out).unwrap().contains("\r\ndef!  display  do  dot\r\n"));
+}
+
+#[test]
+fn editor_matches_parens() {
+    let chars: Vec<char> = "(a (b \")\" c) ; (".chars().collect();
+
+    assert_eq!(matching_paren(&chars, 0), None);
+    assert_eq!(matching_paren(&chars, 3), Some(11));
+    assert_eq!(matching_paren(&chars, 11), Some(3));
+    assert_eq!(matching_paren(&chars, 7), None);
+
+    let chars: Vec<char> = "{1 [(f)]}".chars().collect();
+    assert_eq!(matching_paren(&chars, 0), Some(8));
+    assert_eq!(matching_paren(&chars, 3), Some(7));
+
+    let mut line = LineBuffer::new();
+    line.set("(+ 1 2)");
+    let mut out = Vec::new();
+    render(">> ", &line, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "\r>> \x1b[7m(\x1b[0m+ 1 2)\x1b[K\r\x1b[10C");
+}
@@ -0,0 +1,125 @@
+// Raw mode for the terminal on stdin, so that keys are read one at a time without echo.
+// struct termios and its flags are declared by hand as glibc defines them on x86, x86_64 and
+// aarch64, other targets lay them out differently. Elsewhere RawMode::enable fails and the
+// REPL reads whole lines.
+
+use std::io;
+
+#[cfg(all(target_os = "linux", target_env = "gnu",
+          any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+mod sys {
+    use std::io;
+
+    // struct termios from the glibc headers
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct Termios {
+        pub c_iflag: u32,
+        pub c_oflag: u32,
+        pub c_cflag: u32,
+        pub c_lflag: u32,
+        pub c_line: u8,
+        pub c_cc: [u8; 32],
+        pub c_ispeed: u32,
+        pub c_ospeed: u32,
+    }
+
+    // input flags
+    const ICRNL: u32 = 0o400;
+    const IXON: u32 = 0o2000;
+    // output flags
+    const OPOST: u32 = 0o1;
+    // local flags
+    const ISIG: u32 = 0o1;
+    const ICANON: u32 = 0o2;
+    const ECHO: u32 = 0o10;
+    const IEXTEN: u32 = 0o100000;
+    // indexes in c_cc
+    const VTIME: usize = 5;
+    const VMIN: usize = 6;
+
+    // applies the change once output is written, without discarding typed-ahead input
+    const TCSADRAIN: i32 = 1;
+    const STDIN: i32 = 0;
+
+    extern "C" {
+        fn tcgetattr(fd: i32, termios: *mut Termios) -> i32;
+        fn tcsetattr(fd: i32, action: i32, termios: *const Termios) -> i32;
+    }
+
+    pub fn get() -> io::Result<Termios> {
+        let mut termios = Termios {
+            c_iflag: 0,
+            c_oflag: 0,
+            c_cflag: 0,
+            c_lflag: 0,
+            c_line: 0,
+            c_cc: [0; 32],
+            c_ispeed: 0,
+            c_ospeed: 0,
+        };
+        if unsafe { tcgetattr(STDIN, &mut termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(termios)
+    }
+
+    pub fn set(termios: &Termios) -> io::Result<()> {
+        if unsafe { tcsetattr(STDIN, TCSADRAIN, termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    // No echo, no line buffering, no signals from ^C and ^Z, reads return after one byte
+    pub fn make_raw(termios: &Termios) -> Termios {
+        let mut raw = *termios;
+        raw.c_iflag &= !(ICRNL | IXON);
+        raw.c_oflag &= !OPOST;
+        raw.c_lflag &= !(ECHO | ICANON | ISIG | IEXTEN);
+        raw.c_cc[VMIN] = 1;
+        raw.c_cc[VTIME] = 0;
+        raw
+    }
+}
+
+// other targets, where raw mode can't be enabled
+#[cfg(not(all(target_os = "linux", target_env = "gnu",
+          any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64"))))]
+mod sys {
+    use std::io;
+
+    #[derive(Clone, Copy)]
+    pub struct Termios;
+
+    pub fn get() -> io::Result<Termios> {
+        Err(io::Error::other("raw mode is not supported on this target"))
+    }
+
+    pub fn set(_: &Termios) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn make_raw(termios: &Termios) -> Termios {
+        *termios
+    }
+}
+
+// Puts the terminal in raw mode until dropped
+pub struct RawMode {
+    original: sys::Termios,
+}
+
+impl RawMode {
+    pub fn enable() -> io::Result<RawMode> {
+        let original = sys::get()?;
+        sys::set(&sys::make_raw(&original))?;
+        Ok(RawMode { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = sys::set(&self.original);
+    }
+}
@@ -0,0 +1,12 @@
+// Rustl, an interpreter for a simple Lisp-like language.
+// The Interpreter type is the entry point for embedding it, the lisp module exposes the
+// lower level building blocks (values, environments, the evaluator).
+
+pub mod lisp;
+
+pub use lisp::interpreter::Interpreter;
+pub use lisp::types::{RLType, RLResult, RLNative, Arity};
+pub use lisp::error::{RLError, ErrorKind};
+pub use lisp::context::{Context, CancelToken};
+pub use lisp::convert::{FromLisp, IntoLisp};
+pub use lisp::lex::parse_all;
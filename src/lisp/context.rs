@@ -0,0 +1,159 @@
+use std;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use lisp::env::Env;
+use lisp::eval::apply;
+use lisp::types::*;
+
+// Number of evaluation steps between two reads of the clock when a deadline is set
+const DEADLINE_CHECK_INTERVAL: u32 = 64;
+
+// A CancelToken can be shared with other threads to abort a running evaluation.
+// Clones share the same flag, so cancelling any of them cancels all.
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    flag: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    // Requests the cancellation of the evaluations using this token
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+
+    // Clears a previous cancellation, so that the token can be used again
+    pub fn reset(&self) {
+        self.flag.store(false, Ordering::SeqCst);
+    }
+}
+
+// The Context holds the state of an evaluation that doesn't belong to the Environment.
+// eval checks it at every step, an evaluation that is cancelled or past its deadline
+// stops with an error before performing any further side effect on the Environment.
+#[derive(Default)]
+pub struct Context {
+    cancel_token: Option<CancelToken>,
+    deadline: Option<Instant>,
+    // steps left before the next check of the deadline
+    steps: u32,
+    // environment of the native function being executed
+    caller_env: Option<Env>,
+    // number of lambdas being executed, and the maximum allowed
+    depth: usize,
+    max_depth: Option<usize>,
+}
+
+impl Context {
+    pub fn new() -> Context {
+        Context::default()
+    }
+
+    pub fn set_cancel_token(&mut self, token: CancelToken) {
+        self.cancel_token = Some(token);
+    }
+
+    // Returns the token used by this context, creating one if none was set
+    pub fn cancel_token(&mut self) -> CancelToken {
+        self.cancel_token.get_or_insert_with(CancelToken::new).clone()
+    }
+
+    pub fn clear_cancel_token(&mut self) {
+        self.cancel_token = None;
+    }
+
+    // Evaluations will fail with DeadlineExceeded once the deadline has passed
+    pub fn set_deadline(&mut self, deadline: Instant) {
+        self.deadline = Some(deadline);
+        self.steps = 0;
+    }
+
+    // Sets a deadline of timeout from now
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.set_deadline(Instant::now() + timeout);
+    }
+
+    pub fn clear_deadline(&mut self) {
+        self.deadline = None;
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    // Limits the number of nested lambda calls, evaluations going deeper fail with
+    // LimitExceeded instead of overflowing the stack
+    pub fn set_max_depth(&mut self, max_depth: Option<usize>) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // Called by the evaluator when a lambda starts executing
+    pub fn enter_call(&mut self) -> Result<(), RLError> {
+        if let Some(max) = self.max_depth {
+            if self.depth >= max {
+                let limit = format!("maximum call depth of {}", max);
+                return Err(RLError::new(ErrorKind::LimitExceeded(limit)));
+            }
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    // Called by the evaluator when a lambda returns, successfully or not
+    pub fn leave_call(&mut self) {
+        self.depth -= 1;
+    }
+
+    // The environment a native function was called from, None outside of native calls
+    pub fn caller_env(&self) -> Option<&Env> {
+        self.caller_env.as_ref()
+    }
+
+    // Sets the caller environment, returning the previous one so that it can be restored
+    pub fn set_caller_env(&mut self, env: Option<Env>) -> Option<Env> {
+        std::mem::replace(&mut self.caller_env, env)
+    }
+
+    // Calls a function from a native function, in the environment the native was called from
+    pub fn call(&mut self, func: &RLType, args: Vec<RLType>) -> RLResult {
+        match self.caller_env.clone() {
+            Some(env) => apply(func, args, &env, self),
+            None => error("No environment to call the function in".to_string()),
+        }
+    }
+
+    // Called by the evaluator at every step, returns an error if the evaluation has to stop.
+    // The token is checked every time, the clock only every few steps.
+    pub fn check(&mut self) -> Result<(), RLError> {
+        if let Some(ref token) = self.cancel_token {
+            if token.is_cancelled() {
+                return Err(RLError::new(ErrorKind::Cancelled));
+            }
+        }
+
+        if let Some(deadline) = self.deadline {
+            if self.steps == 0 {
+                if Instant::now() >= deadline {
+                    return Err(RLError::new(ErrorKind::DeadlineExceeded));
+                }
+                self.steps = DEADLINE_CHECK_INTERVAL;
+            }
+            self.steps -= 1;
+        }
+
+        Ok(())
+    }
+}
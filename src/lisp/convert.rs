@@ -0,0 +1,419 @@
+// Conversions between RLType values and Rust types, used to write builtins as plain
+// Rust functions: the arguments are converted with FromLisp and the result with IntoLisp.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use lisp::types::*;
+
+// A Rust type that can be extracted from a RLType value
+pub trait FromLisp: Sized {
+    fn from_lisp(value: RLType) -> Result<Self, RLError>;
+}
+
+// A Rust type that can be turned into a RLType value
+pub trait IntoLisp {
+    fn into_lisp(self) -> RLType;
+}
+
+// The return type of a builtin: either a value or a Result carrying an error
+pub trait IntoLispResult {
+    fn into_lisp_result(self) -> RLResult;
+}
+
+impl<T: IntoLisp> IntoLispResult for T {
+    fn into_lisp_result(self) -> RLResult {
+        Ok(self.into_lisp())
+    }
+}
+
+impl<T: IntoLisp> IntoLispResult for Result<T, RLError> {
+    fn into_lisp_result(self) -> RLResult {
+        self.map(IntoLisp::into_lisp)
+    }
+}
+
+// Error for a value that doesn't have the expected type
+pub fn type_error(expected: &str, value: &RLType) -> RLError {
+    RLError::type_mismatch(expected, value)
+}
+
+// Adds the position of a value to an error raised while converting it
+fn element_error(position: &str, index: usize, error: RLError) -> RLError {
+    error.with_context(format!("{} {}", position, index + 1))
+}
+
+impl FromLisp for RLType {
+    fn from_lisp(value: RLType) -> Result<RLType, RLError> {
+        Ok(value)
+    }
+}
+
+impl IntoLisp for RLType {
+    fn into_lisp(self) -> RLType {
+        self
+    }
+}
+
+impl FromLisp for f64 {
+    fn from_lisp(value: RLType) -> Result<f64, RLError> {
+        match value {
+            RLType::Number(n) => Ok(n),
+            _ => Err(type_error("number", &value)),
+        }
+    }
+}
+
+impl FromLisp for f32 {
+    fn from_lisp(value: RLType) -> Result<f32, RLError> {
+        f64::from_lisp(value).map(|n| n as f32)
+    }
+}
+
+impl IntoLisp for f64 {
+    fn into_lisp(self) -> RLType {
+        RLType::Number(self)
+    }
+}
+
+impl IntoLisp for f32 {
+    fn into_lisp(self) -> RLType {
+        RLType::Number(f64::from(self))
+    }
+}
+
+// Integers are numbers without a fractional part that fit in the target type. The upper bound
+// is MAX + 1, a power of two that floats represent exactly: MAX itself rounds up to it for the
+// 64 bit types, so comparing with MAX would let it through and the cast would saturate.
+macro_rules! impl_integer {
+    ($($int:ty),*) => {
+        $(
+            impl FromLisp for $int {
+                fn from_lisp(value: RLType) -> Result<$int, RLError> {
+                    match value {
+                        RLType::Number(n) if n.fract() == 0.0 &&
+                                             n >= <$int>::MIN as f64 &&
+                                             n < <$int>::MAX as f64 + 1.0 => Ok(n as $int),
+                        _ => Err(type_error(concat!("integer (", stringify!($int), ")"), &value)),
+                    }
+                }
+            }
+
+            impl IntoLisp for $int {
+                fn into_lisp(self) -> RLType {
+                    RLType::Number(self as f64)
+                }
+            }
+        )*
+    }
+}
+
+impl_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl FromLisp for bool {
+    fn from_lisp(value: RLType) -> Result<bool, RLError> {
+        match value {
+            RLType::True => Ok(true),
+            RLType::False => Ok(false),
+            _ => Err(type_error("boolean", &value)),
+        }
+    }
+}
+
+impl IntoLisp for bool {
+    fn into_lisp(self) -> RLType {
+        if self { RLType::True } else { RLType::False }
+    }
+}
+
+impl FromLisp for String {
+    fn from_lisp(value: RLType) -> Result<String, RLError> {
+        match value {
+            RLType::Str(text) => Ok(text),
+            _ => Err(type_error("string", &value)),
+        }
+    }
+}
+
+impl IntoLisp for String {
+    fn into_lisp(self) -> RLType {
+        RLType::Str(self)
+    }
+}
+
+impl IntoLisp for &str {
+    fn into_lisp(self) -> RLType {
+        RLType::Str(self.to_string())
+    }
+}
+
+impl FromLisp for char {
+    fn from_lisp(value: RLType) -> Result<char, RLError> {
+        match value {
+            RLType::Char(c) => Ok(c),
+            _ => Err(type_error("char", &value)),
+        }
+    }
+}
+
+impl IntoLisp for char {
+    fn into_lisp(self) -> RLType {
+        RLType::Char(self)
+    }
+}
+
+impl IntoLisp for () {
+    fn into_lisp(self) -> RLType {
+        RLType::Nil
+    }
+}
+
+impl<T: FromLisp> FromLisp for Vec<T> {
+    fn from_lisp(value: RLType) -> Result<Vec<T>, RLError> {
+        match value {
+            RLType::Vector(values) => {
+                Vec::from_lisp(RLType::List(values.iter().cloned().collect()))
+            },
+            RLType::List(values) => {
+                values.into_iter()
+                    .enumerate()
+                    .map(|(i, v)| T::from_lisp(v).map_err(|e| element_error("element", i, e)))
+                    .collect()
+            },
+            _ => Err(type_error("list or vector", &value)),
+        }
+    }
+}
+
+impl<T: IntoLisp> IntoLisp for Vec<T> {
+    fn into_lisp(self) -> RLType {
+        RLType::List(self.into_iter().map(IntoLisp::into_lisp).collect())
+    }
+}
+
+// nil is None, any other value is converted to Some
+impl<T: FromLisp> FromLisp for Option<T> {
+    fn from_lisp(value: RLType) -> Result<Option<T>, RLError> {
+        match value {
+            RLType::Nil => Ok(None),
+            _ => T::from_lisp(value).map(Some),
+        }
+    }
+}
+
+impl<T: IntoLisp> IntoLisp for Option<T> {
+    fn into_lisp(self) -> RLType {
+        match self {
+            Some(value) => value.into_lisp(),
+            None => RLType::Nil,
+        }
+    }
+}
+
+// Maps come from map values or from association lists, lists of (key value) pairs.
+// Keys can be strings or symbols.
+impl<T: FromLisp> FromLisp for HashMap<String, T> {
+    fn from_lisp(value: RLType) -> Result<HashMap<String, T>, RLError> {
+        let pairs: Vec<RLType> = match value {
+            RLType::List(pairs) => pairs,
+            RLType::Map(entries) => {
+                entries.iter()
+                    .map(|(key, value)| RLType::List(vec![key.clone(), value.clone()]))
+                    .collect()
+            },
+            _ => return Err(type_error("map or association list", &value)),
+        };
+
+        let mut map = HashMap::new();
+        for (i, pair) in pairs.into_iter().enumerate() {
+            let (key, value) = match pair {
+                RLType::List(ref entry) if entry.len() == 2 => (entry[0].clone(), entry[1].clone()),
+                _ => return Err(element_error("entry", i, type_error("(key value) pair", &pair))),
+            };
+            let key = match key {
+                RLType::Str(key) | RLType::Symbol(key) => key,
+                _ => {
+                    return Err(element_error("entry", i, type_error("string or symbol key", &key)))
+                },
+            };
+            let value = T::from_lisp(value).map_err(|e| element_error("entry", i, e))?;
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+// Entries are sorted by key, so that the resulting list doesn't depend on the hash order
+impl<K: IntoLisp + Ord + Hash, T: IntoLisp> IntoLisp for HashMap<K, T> {
+    fn into_lisp(self) -> RLType {
+        let mut entries: Vec<(K, T)> = self.into_iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries.into_lisp()
+    }
+}
+
+// Tuples are lists with exactly one element for each field
+macro_rules! impl_tuple {
+    ($len:expr; $($name:ident),+) => {
+        impl<$($name: FromLisp),+> FromLisp for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn from_lisp(value: RLType) -> Result<($($name,)+), RLError> {
+                let values = match value {
+                    RLType::List(ref values) if values.len() == $len => values.clone(),
+                    _ => return Err(type_error(concat!("list of ", $len, " elements"), &value)),
+                };
+                let mut values = values.into_iter().enumerate();
+                $(
+                    let (i, $name) = values.next().unwrap();
+                    let $name = $name::from_lisp($name)
+                        .map_err(|e| element_error("element", i, e))?;
+                )+
+                Ok(($($name,)+))
+            }
+        }
+
+        impl<$($name: IntoLisp),+> IntoLisp for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn into_lisp(self) -> RLType {
+                let ($($name,)+) = self;
+                RLType::List(vec![$($name.into_lisp()),+])
+            }
+        }
+    }
+}
+
+impl_tuple!(1; A);
+impl_tuple!(2; A, B);
+impl_tuple!(3; A, B, C);
+impl_tuple!(4; A, B, C, D);
+
+// A Rust function that can be turned into a native builtin. Args is the tuple of its
+// argument types, the arity and the conversion of each argument are checked on every call.
+pub trait IntoNative<Args> {
+    fn into_native(self, name: &str) -> RLNative;
+}
+
+macro_rules! impl_into_native {
+    ($len:expr; $($arg:ident),*) => {
+        impl<Func, Ret, $($arg),*> IntoNative<($($arg,)*)> for Func
+            where Func: Fn($($arg),*) -> Ret + 'static,
+                  Ret: IntoLispResult,
+                  $($arg: FromLisp),*
+        {
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn into_native(self, name: &str) -> RLNative {
+                let fn_name = name.to_string();
+                RLNative::new(name, Arity::Exact($len), move |_, args| {
+                    let mut args = args.into_iter().enumerate();
+                    $(
+                        let (i, $arg) = args.next().unwrap();
+                        let $arg = $arg::from_lisp($arg).map_err(|e| {
+                            e.with_context(format!("{}: argument {}", fn_name, i + 1))
+                        })?;
+                    )*
+                    self($($arg),*).into_lisp_result()
+                })
+            }
+        }
+    }
+}
+
+impl_into_native!(0; );
+impl_into_native!(1; A);
+impl_into_native!(2; A, B);
+impl_into_native!(3; A, B, C);
+impl_into_native!(4; A, B, C, D);
+impl_into_native!(5; A, B, C, D, E);
+
+#[test]
+fn convert_numbers() {
+    assert_eq!(f64::from_lisp(RLType::Number(1.5)).ok(), Some(1.5));
+    assert_eq!(i64::from_lisp(RLType::Number(-3.0)).ok(), Some(-3));
+    assert!(i64::from_lisp(RLType::Number(1.5)).is_err());
+    assert!(u8::from_lisp(RLType::Number(300.0)).is_err());
+    assert!(u32::from_lisp(RLType::Number(-1.0)).is_err());
+    assert_eq!(u8::from_lisp(RLType::Number(255.0)).ok(), Some(255));
+    assert!(u8::from_lisp(RLType::Number(256.0)).is_err());
+
+    // 2^63 and 2^64 are the float values of i64::MAX and u64::MAX, but don't fit
+    let two_63 = 9_223_372_036_854_775_808.0;
+    assert!(i64::from_lisp(RLType::Number(two_63)).is_err());
+    assert_eq!(i64::from_lisp(RLType::Number(-two_63)).ok(), Some(i64::MIN));
+    assert_eq!(i64::from_lisp(RLType::Number(two_63 - 1024.0)).ok(), Some(i64::MAX - 1023));
+    assert!(u64::from_lisp(RLType::Number(2.0 * two_63)).is_err());
+    assert_eq!(u64::from_lisp(RLType::Number(2.0 * two_63 - 2048.0)).ok(), Some(u64::MAX - 2047));
+
+    match 42usize.into_lisp() {
+        RLType::Number(n) => assert_eq!(n, 42.0),
+        _ => panic!("not a number"),
+    }
+}
+
+#[test]
+fn convert_type_mismatch_message() {
+    match f64::from_lisp(RLType::Str("a".to_string())) {
+        Err(e) => assert_eq!(e.get_message(), "expected number, got string \"a\""),
+        Ok(_) => panic!("a string is not a number"),
+    }
+}
+
+#[test]
+fn convert_collections() {
+    let list = vec![1.0f32, 2.0, 3.0].into_lisp();
+    assert_eq!(Vec::<f32>::from_lisp(list).ok(), Some(vec![1.0, 2.0, 3.0]));
+
+    let mixed = RLType::List(vec![RLType::Number(1.0), RLType::True]);
+    match Vec::<f32>::from_lisp(mixed) {
+        Err(e) => assert_eq!(e.get_message(), "element 2: expected number, got boolean #t"),
+        Ok(_) => panic!("#t is not a number"),
+    }
+
+    assert_eq!(Option::<bool>::from_lisp(RLType::Nil).ok(), Some(None));
+    assert_eq!(Option::<bool>::from_lisp(RLType::True).ok(), Some(Some(true)));
+
+    let pair = ("a".to_string(), 2i32).into_lisp();
+    assert_eq!(<(String, i32)>::from_lisp(pair).ok(), Some(("a".to_string(), 2)));
+    assert!(<(String, i32)>::from_lisp(RLType::List(vec![])).is_err());
+}
+
+#[test]
+fn convert_maps() {
+    let mut map = HashMap::new();
+    map.insert("b".to_string(), 2i32);
+    map.insert("a".to_string(), 1i32);
+
+    let alist = map.clone().into_lisp();
+    assert_eq!(format!("{:?}", alist), "((\"a\" 1) (\"b\" 2))");
+    assert_eq!(HashMap::<String, i32>::from_lisp(alist).ok(), Some(map.clone()));
+
+    let entries = vec![("a", 1), ("b", 2)].into_iter().map(|(k, v)| (k.into_lisp(), v.into_lisp()));
+    assert_eq!(HashMap::<String, i32>::from_lisp(RLType::Map(entries.collect())).ok(), Some(map));
+}
+
+#[test]
+fn convert_native_functions() {
+    use lisp::context::Context;
+
+    fn hypot(a: f64, b: f64) -> f64 {
+        (a * a + b * b).sqrt()
+    }
+
+    let native = hypot.into_native("hypot");
+    let mut ctx = Context::new();
+
+    match native.call(&mut ctx, vec![RLType::Number(3.0), RLType::Number(4.0)]) {
+        Ok(RLType::Number(n)) => assert_eq!(n, 5.0),
+        _ => panic!("wrong result"),
+    }
+
+    match native.call(&mut ctx, vec![RLType::Number(3.0), RLType::Nil]) {
+        Err(e) => assert_eq!(e.get_message(), "hypot: argument 2: expected number, got nil"),
+        Ok(_) => panic!("nil is not a number"),
+    }
+
+    match native.call(&mut ctx, vec![RLType::Number(3.0)]) {
+        Err(e) => assert_eq!(e.get_message(),
+                             "Invalid number of arguments for hypot: expected 2, got 1"),
+        Ok(_) => panic!("hypot takes two arguments"),
+    }
+}
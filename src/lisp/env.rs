@@ -3,9 +3,20 @@ use std::rc::Rc;
 use std::cell::RefCell;
 
 use lisp::types::*;
+use lisp::context::Context;
+use lisp::convert::IntoNative;
 
 use lisp::modules::comparison;
+use lisp::modules::base;
 use lisp::modules::math;
+use lisp::modules::types;
+use lisp::modules::string;
+use lisp::modules::format;
+use lisp::modules::chars;
+use lisp::modules::maps;
+use lisp::modules::vectors;
+use lisp::modules::sets;
+use lisp::modules::atoms;
 
 pub struct Environment {
     map: HashMap<String, RLType>,
@@ -34,13 +45,21 @@ impl Environment {
     }
 
     // A default Environment with core functions
+    #[allow(clippy::should_implement_trait)]
     pub fn default() -> Env {
         let mut env = Environment::new();
 
-        // TODO: this should be in a separate file
-        env.borrow_mut().insert("pi".to_string(), RLType::Number(3.14159265));
         math::add_module(&mut env);
         comparison::add_module(&mut env);
+        base::add_module(&mut env);
+        types::add_module(&mut env);
+        string::add_module(&mut env);
+        format::add_module(&mut env);
+        chars::add_module(&mut env);
+        maps::add_module(&mut env);
+        vectors::add_module(&mut env);
+        sets::add_module(&mut env);
+        atoms::add_module(&mut env);
 
         env
     }
@@ -55,14 +74,41 @@ impl Environment {
             None => {
                 match self.outer {
                     Some(ref env) => env.borrow().lookup(name),
-                    None => error(format!("No value for given key: {}", name)),
+                    None => Err(RLError::unbound_symbol(name)),
                 }
             },
         }
     }
 
+    // names returns the names bound in this environment and in the outer ones, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = match self.outer {
+            Some(ref env) => env.borrow().names(),
+            None => Vec::new(),
+        };
+        names.extend(self.map.keys().cloned());
+        names.sort();
+        names.dedup();
+        names
+    }
+
     // insert puts a value in the environment with the specified key (name)
     pub fn insert(&mut self, name: String, value: RLType) {
         self.map.insert(name, value);
     }
+
+    // insert_native binds a Rust closure as a builtin function
+    pub fn insert_native<F>(&mut self, name: &str, arity: Arity, func: F)
+        where F: Fn(&mut Context, Vec<RLType>) -> RLResult + 'static
+    {
+        self.insert(name.to_string(), RLType::Native(RLNative::new(name, arity, func)));
+    }
+
+    // insert_typed binds a plain Rust function as a builtin, its arguments and result are
+    // converted with the FromLisp and IntoLisp traits
+    pub fn insert_typed<F, Args>(&mut self, name: &str, func: F)
+        where F: IntoNative<Args>
+    {
+        self.insert(name.to_string(), RLType::Native(func.into_native(name)));
+    }
 }
@@ -0,0 +1,270 @@
+use std;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use lisp::lex::{ParseError, Span};
+use lisp::types::{RLType, RLResult, Arity};
+
+// The kind of an error, host code can match on it to handle specific failures
+#[derive(Debug)]
+pub enum ErrorKind {
+    Message(String),
+    InvalidValue(RLType),
+    // A symbol without a value in the environment
+    UnboundSymbol(String),
+    // A value of the wrong type, expected describes the accepted types
+    TypeMismatch { expected: String, actual: RLType },
+    // A function called with the wrong number of arguments
+    Arity { name: String, expected: Arity, actual: usize },
+    // Input that can't be read, with the position of the offending token
+    Parse(ParseError, Span),
+    // An index past the end of a string or a sequence, as it was written
+    IndexOutOfRange { index: f64, length: usize },
+    // A division or remainder by zero
+    DivisionByZero,
+    // A value raised by a script with throw
+    Thrown(RLType),
+    // A limit set in the Context, such as the maximum call depth, was reached
+    LimitExceeded(String),
+    // The evaluation was stopped through its CancelToken
+    Cancelled,
+    // The evaluation ran past the deadline set in its Context
+    DeadlineExceeded,
+}
+
+// Errors carry their kind, a context describing where the error happened in the arguments of
+// a function (outermost first) and a trace of the lambdas being executed (innermost first).
+#[derive(Debug)]
+pub struct RLError {
+    kind: Box<ErrorKind>,
+    context: Vec<String>,
+    trace: Vec<String>,
+}
+
+impl RLError {
+    pub fn new(kind: ErrorKind) -> RLError {
+        RLError {
+            kind: Box::new(kind),
+            context: Vec::new(),
+            trace: Vec::new(),
+        }
+    }
+
+    pub fn message(message: String) -> RLError {
+        RLError::new(ErrorKind::Message(message))
+    }
+
+    pub fn unbound_symbol(name: &str) -> RLError {
+        RLError::new(ErrorKind::UnboundSymbol(name.to_string()))
+    }
+
+    pub fn type_mismatch(expected: &str, actual: &RLType) -> RLError {
+        RLError::new(ErrorKind::TypeMismatch {
+            expected: expected.to_string(),
+            actual: actual.clone(),
+        })
+    }
+
+    pub fn arity(name: &str, expected: Arity, actual: usize) -> RLError {
+        RLError::new(ErrorKind::Arity {
+            name: name.to_string(),
+            expected,
+            actual,
+        })
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn context(&self) -> &[String] {
+        &self.context
+    }
+
+    // Names of the lambdas that were executing when the error happened, innermost first
+    pub fn trace(&self) -> &[String] {
+        &self.trace
+    }
+
+    // Adds a description of where the error happened, such as the argument being converted
+    pub fn with_context(mut self, context: String) -> RLError {
+        self.context.insert(0, context);
+        self
+    }
+
+    // Adds the name of a lambda the error is propagating through
+    pub fn with_frame(mut self, frame: String) -> RLError {
+        self.trace.push(frame);
+        self
+    }
+
+    // Returns true for parse errors caused by input that ends in the middle of a form,
+    // which could be completed by reading more input
+    pub fn is_incomplete_input(&self) -> bool {
+        matches!(*self.kind,
+                 ErrorKind::Parse(ParseError::EOFReached, _) |
+                 ErrorKind::Parse(ParseError::UnterminatedString, _))
+    }
+
+    // The error message with its context, without the trace
+    pub fn get_message(&self) -> String {
+        let mut message = String::new();
+        for context in &self.context {
+            message.push_str(context);
+            message.push_str(": ");
+        }
+        message.push_str(&self.kind.to_string());
+        message
+    }
+}
+
+impl From<ErrorKind> for RLError {
+    fn from(kind: ErrorKind) -> RLError {
+        RLError::new(kind)
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match *self {
+            ErrorKind::Message(ref s) => write!(f, "{}", s),
+            ErrorKind::InvalidValue(ref v) => write!(f, "{:?}", v),
+            ErrorKind::UnboundSymbol(ref name) => write!(f, "No value for given key: {}", name),
+            ErrorKind::TypeMismatch { ref expected, actual: RLType::Nil } => {
+                write!(f, "expected {}, got nil", expected)
+            },
+            ErrorKind::TypeMismatch { ref expected, ref actual } => {
+                write!(f, "expected {}, got {} {:?}", expected, actual.type_name(), actual)
+            },
+            ErrorKind::Arity { ref name, expected, actual } => {
+                write!(f, "Invalid number of arguments for {}: expected {}, got {}",
+                       name, expected, actual)
+            },
+            ErrorKind::Parse(error, span) => {
+                let reason = match error {
+                    ParseError::UnbalancedParens => "unbalanced parentheses",
+                    ParseError::EOFReached => "unexpected end of input",
+                    ParseError::UnterminatedString => "unterminated string",
+                    ParseError::InvalidInterpolation => "expected one expression in ${}",
+                    ParseError::InvalidChar => "invalid character literal",
+                    ParseError::MissingMapValue => "expected a value for each key in {}",
+                };
+                write!(f, "Parse error at line {}, column {}: {}", span.line, span.column, reason)
+            },
+            ErrorKind::IndexOutOfRange { index, length } => {
+                write!(f, "Index {} out of range for length {}", index, length)
+            },
+            ErrorKind::DivisionByZero => write!(f, "Division by zero"),
+            ErrorKind::Thrown(ref value) => write!(f, "Uncaught value: {:?}", value),
+            ErrorKind::LimitExceeded(ref limit) => write!(f, "Limit exceeded: {}", limit),
+            ErrorKind::Cancelled => write!(f, "Evaluation cancelled"),
+            ErrorKind::DeadlineExceeded => write!(f, "Evaluation deadline exceeded"),
+        }
+    }
+}
+
+impl Display for RLError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.get_message())?;
+        for frame in &self.trace {
+            write!(f, "\n    in {}", frame)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for RLError {}
+
+pub fn error(message: String) -> RLResult {
+    Err(RLError::message(message))
+}
+
+#[test]
+fn errors_have_structured_kinds() {
+    use lisp::interpreter::Interpreter;
+
+    let mut interp = Interpreter::new();
+
+    match interp.eval_str("(+ 1 undefined)").map_err(|e| *e.kind) {
+        Err(ErrorKind::UnboundSymbol(ref name)) => assert_eq!(name, "undefined"),
+        _ => panic!("expected an unbound symbol"),
+    }
+
+    match interp.eval_str("(+ 1 \"two\")").map_err(|e| *e.kind) {
+        Err(ErrorKind::TypeMismatch { ref expected, actual: RLType::Str(ref s) }) => {
+            assert_eq!(expected, "number");
+            assert_eq!(s, "two");
+        },
+        _ => panic!("expected a type mismatch"),
+    }
+
+    match interp.eval_str("(< 1)").map_err(|e| *e.kind) {
+        Err(ErrorKind::Arity { ref name, expected: Arity::AtLeast(2), actual: 1 }) => {
+            assert_eq!(name, "<")
+        },
+        _ => panic!("expected an arity error"),
+    }
+
+    match interp.eval_str("(throw 42)").map_err(|e| *e.kind) {
+        Err(ErrorKind::Thrown(RLType::Number(n))) => assert_eq!(n, 42.0),
+        _ => panic!("expected a thrown value"),
+    }
+}
+
+#[test]
+fn parse_errors_have_spans() {
+    use lisp::interpreter::Interpreter;
+
+    let mut interp = Interpreter::new();
+
+    match interp.eval_str("\n   )").map_err(|e| *e.kind) {
+        Err(ErrorKind::Parse(ParseError::UnbalancedParens, span)) => {
+            assert_eq!((span.line, span.column), (2, 4));
+        },
+        _ => panic!("expected unbalanced parentheses"),
+    }
+
+    let error = interp.eval_str("  (+ 1 \"a").unwrap_err();
+    match *error.kind {
+        ErrorKind::Parse(ParseError::UnterminatedString, span) => {
+            assert_eq!((span.start, span.end), (7, 9));
+            assert_eq!((span.line, span.column), (1, 8));
+        },
+        _ => panic!("expected an unterminated string"),
+    }
+    assert_eq!(error.to_string(), "Parse error at line 1, column 8: unterminated string");
+}
+
+#[test]
+fn errors_trace_lambda_calls() {
+    use lisp::interpreter::Interpreter;
+
+    let mut interp = Interpreter::new();
+    assert!(interp.eval_str("(def! inner (lambda (x) (+ x \"a\")))").is_ok());
+    assert!(interp.eval_str("(def! outer (lambda (x) (inner x)))").is_ok());
+
+    let error = interp.eval_str("(outer 1)").unwrap_err();
+    assert_eq!(error.trace(), &["inner".to_string(), "outer".to_string()]);
+    assert_eq!(error.to_string(),
+               "expected number, got string \"a\"\n    in inner\n    in outer");
+
+    // the error can be used as a std error
+    let boxed: Box<dyn Error> = Box::new(error);
+    assert_eq!(boxed.to_string().lines().next(), Some("expected number, got string \"a\""));
+}
+
+#[test]
+fn errors_report_exceeded_limits() {
+    use lisp::interpreter::Interpreter;
+
+    let mut interp = Interpreter::new();
+    interp.context().set_max_depth(Some(50));
+    assert!(interp.eval_str("(def! down (lambda (n) (if (= n 0) 0 (down (- n 1)))))").is_ok());
+
+    assert!(interp.eval_str("(down 40)").is_ok());
+    match interp.eval_str("(down 100)").map_err(|e| *e.kind) {
+        Err(ErrorKind::LimitExceeded(_)) => (),
+        _ => panic!("expected the depth limit to be exceeded"),
+    }
+    assert_eq!(interp.context().depth(), 0);
+}
@@ -1,11 +1,16 @@
+use std::rc::Rc;
+
 use lisp::lex::{tokenize, parse_form};
 use lisp::types::*;
 use lisp::env::*;
+use lisp::context::Context;
+
+pub fn eval(ast: RLType, env: &Env, ctx: &mut Context) -> RLResult {
+    ctx.check()?;
 
-pub fn eval(ast: RLType, env: &Env) -> RLResult {
     match ast {
         RLType::Symbol(ref name) => env.borrow().lookup(name),
-        RLType::List(tokens) => eval_list(tokens, env),
+        RLType::List(tokens) => eval_list(tokens, env, ctx),
         _ => Ok(ast),
     }
 }
@@ -13,96 +18,105 @@ pub fn eval(ast: RLType, env: &Env) -> RLResult {
 // Evaluates the list
 // if the first element is a function or keyword, it executes that, otherwise returns
 // the list itself
-fn eval_list(mut tokens: Vec<RLType>, env: &Env) -> RLResult {
+fn eval_list(mut tokens: Vec<RLType>, env: &Env, ctx: &mut Context) -> RLResult {
     // empty list -> no action
     if tokens.is_empty() {
         return Ok(RLType::List(tokens));
     }
 
-    let mut first = tokens.remove(0);
-
-    if !first.is_atom() {
-        first = try!(eval(first, env));
-    }
+    let first = tokens.remove(0);
 
-    match first {
+    let func = match first {
         RLType::Symbol(name) => {
-            if let Ok(value) = eval_core(&name, &mut tokens, env) {
-                return Ok(value);
+            if is_keyword(&name) {
+                return eval_core(&name, &mut tokens, env, ctx);
             }
-            eval_proc(&name, tokens, env)
+            return eval_proc(&name, tokens, env, ctx);
         },
-        RLType::Lambda(lambda) => {
-            match make_atomic(tokens, env) {
-                Ok(RLType::List(ref mut args)) => eval_exec_lambda(lambda, args, env),
-                Ok(v) => error(format!("args are not a list: {}", v)),
-                Err(e) => Err(e),
-            }
-        }
-        _ => error(format!("Element is not a function: {}", first)),
+        RLType::List(_) => eval(first, env, ctx)?,
+        _ => first,
+    };
+
+    if !func.is_function() {
+        return Err(RLError::type_mismatch("function", &func));
     }
+    let args = eval_args(tokens, env, ctx)?;
+    apply(&func, args, env, ctx)
 }
 
-fn eval_core(keyword: &str, args: &mut Vec<RLType>, env: &Env) -> RLResult {
+// Names of the special forms handled by eval_core
+pub const KEYWORDS: &[&str] = &["do", "if", "def!", "lambda", "list"];
+
+fn is_keyword(name: &str) -> bool {
+    KEYWORDS.contains(&name)
+}
+
+fn eval_core(keyword: &str, args: &mut Vec<RLType>, env: &Env, ctx: &mut Context) -> RLResult {
     match keyword {
-        "do" => eval_do(args, env),
-        "if" => eval_if(args, env),
-        "def!" => eval_def(args, env),
+        "do" => eval_do(args, env, ctx),
+        "if" => eval_if(args, env, ctx),
+        "def!" => eval_def(args, env, ctx),
         "lambda" => eval_create_lambda(args),
-        "list" => eval_make_list(args, env),
+        "list" => eval_make_list(args, env, ctx),
         _ => error(format!("Not a keyword: {}", keyword)),
     }
 }
 
-fn eval_proc(name: &str, tokens: Vec<RLType>, env: &Env) -> RLResult {
+fn eval_proc(name: &str, tokens: Vec<RLType>, env: &Env, ctx: &mut Context) -> RLResult {
     // test with: (def! fibo ( lambda (n) (if (<= n 2) n (+ (fibo (- n 1)) (fibo (- n 2))))))
     let executable = env.borrow().lookup(name);
 
     match executable {
-        Ok(RLType::Proc(func)) => {
-            match make_atomic(tokens, env) {
-                Ok(RLType::List(args)) => func(args),
-                Ok(v) => error(format!("args are not a list: {}", v)),
-                Err(e) => Err(e),
-            }
+        Ok(ref func) if func.is_function() => {
+            let args = eval_args(tokens, env, ctx)?;
+            apply(func, args, env, ctx)
         },
-        Ok(RLType::Lambda(lambda)) => {
-            match make_atomic(tokens, env) {
-                Ok(RLType::List(ref mut args)) => eval_exec_lambda(lambda, args, env),
-                Ok(v) => error(format!("args are not a list: {}", v)),
-                Err(e) => Err(e),
-            }
-        },
-        _ => error(format!("Not a function: {}", name))
+        Ok(value) => Err(RLError::type_mismatch("function", &value).with_context(name.to_string())),
+        Err(e) => Err(e),
     }
 }
 
-fn eval_exec_lambda(l: RLClosure, args: &mut Vec<RLType>, outer: &Env) -> RLResult {
-    if l.bindings.len() != args.len() {
-        return error(format!("Invalid number of arguments for lambda: {}", args.len()));
+// Calls a function with arguments that are already evaluated.
+// Lambdas are executed in a new environment on top of env.
+pub fn apply(func: &RLType, args: Vec<RLType>, env: &Env, ctx: &mut Context) -> RLResult {
+    match *func {
+        RLType::Proc(func) => func(args),
+        RLType::Native(ref native) => {
+            // natives can call back into functions through the context
+            let outer = ctx.set_caller_env(Some(env.clone()));
+            let result = native.call(ctx, args);
+            ctx.set_caller_env(outer);
+            result
+        },
+        RLType::Lambda(ref lambda) => eval_exec_lambda(lambda, args, env, ctx),
+        _ => Err(RLError::type_mismatch("function", func)),
     }
+}
+
+fn eval_exec_lambda(l: &RLClosure, args: Vec<RLType>, outer: &Env, ctx: &mut Context) -> RLResult {
+    let name = l.name.clone().unwrap_or_else(|| "lambda".to_string());
+
+    Arity::Exact(l.bindings.len()).check(&name, args.len())?;
 
     let lambda_env = Environment::new_with_outer(outer);
     // bind the args to the environment
-    for i in 0..l.bindings.len() {
-        let atom_arg = try!(eval(args.remove(0), outer));
-        lambda_env.borrow_mut().insert(l.bindings[i].clone(), atom_arg);
+    for (name, value) in l.bindings.iter().zip(args) {
+        lambda_env.borrow_mut().insert(name.clone(), value);
     }
 
-    // executes the lambda
-    eval(*l.ast, &lambda_env)
+    // executes the lambda, errors record it in their trace
+    ctx.enter_call()?;
+    let result = eval((*l.ast).clone(), &lambda_env, ctx);
+    ctx.leave_call();
+
+    result.map_err(|e| e.with_frame(name))
 }
 
 // lambda keyword
 // usage: lambda (params) (body)
 // returns a closure, params should be symbols
 fn eval_create_lambda( args: &mut Vec<RLType>) -> RLResult {
-
-    if args.len() > 2 || args.len() < 1 {
-        return error(format!(
-            "Invalid number of parameters for lambda: {}",
-            args.len()))
-    }
+    Arity::Range(1, 2).check("lambda", args.len())?;
 
     let mut params: Vec<String> = Vec::new();
 
@@ -112,104 +126,107 @@ fn eval_create_lambda( args: &mut Vec<RLType>) -> RLResult {
             for value in values {
                 match value {
                     RLType::Symbol(ref name) => params.push(name.to_owned()),
-                    _ => return error(format!("Parameter is not a symbol: {}", value)),
+                    _ => return error(format!("Parameter is not a symbol: {:?}", value)),
                 }
             }
         }
     }
 
     let lambda = RLClosure {
-        ast: Box::new(args.remove(0)),
-        bindings: params
+        ast: Rc::new(args.remove(0)),
+        bindings: params,
+        name: None,
     };
 
     Ok(RLType::Lambda(lambda))
 }
 
 // Implementation for def
-// usage: (def! name value ...)
-fn eval_def(args: &mut Vec<RLType>, env: &Env) -> RLResult {
+// usage: (def! name value)
+fn eval_def(args: &mut Vec<RLType>, env: &Env, ctx: &mut Context) -> RLResult {
+    Arity::Exact(2).check("def!", args.len())?;
+
     // Check for a symbol as first argument
     if let RLType::Symbol(name) = args.remove(0) {
-        let value = try!(eval(args.remove(0), env));
+        let value = match eval(args.remove(0), env, ctx)? {
+            // anonymous lambdas take the name they are bound to
+            RLType::Lambda(ref lambda) if lambda.name.is_none() => {
+                RLType::Lambda(RLClosure { name: Some(name.clone()), ..lambda.clone() })
+            },
+            value => value,
+        };
         env.borrow_mut().insert(name, value);
         Ok(RLType::Nil)
     } else {
-        error(format!("def!: key is not a symbol"))
+        error("def!: key is not a symbol".to_string())
     }
 }
 
-fn eval_do(args: &mut Vec<RLType>, env: &Env) -> RLResult {
+fn eval_do(args: &mut Vec<RLType>, env: &Env, ctx: &mut Context) -> RLResult {
+    Arity::AtLeast(1).check("do", args.len())?;
+
     while args.len() > 1 {
         let term = args.remove(0);
-        let _ = eval(term, env);
+        eval(term, env, ctx)?;
     }
     // eval and return last element
-    eval(args.remove(0), env)
+    eval(args.remove(0), env, ctx)
 }
 
 // Implementation for list
 // usage: (list v1 v2 ...)
-fn eval_make_list(args: &Vec<RLType>, env: &Env) -> RLResult {
-    match make_atomic(args.clone(), env) {
-        Ok(RLType::List(elements)) => Ok(RLType::List(elements)),
-        Ok(v) => error(format!("list eval error: {}", v)),
-        Err(e) => Err(e),
-    }
+fn eval_make_list(args: &[RLType], env: &Env, ctx: &mut Context) -> RLResult {
+    eval_args(args.to_vec(), env, ctx).map(RLType::List)
 }
 
 // Implementation for if
 // usage: (if test eval_if_true [eval_if_false])
-fn eval_if(args: &mut Vec<RLType>, env: &Env) -> RLResult {
-    let has_else = if args.len() == 3 {
-        true
-    } else {
-        false
-    };
+fn eval_if(args: &mut Vec<RLType>, env: &Env, ctx: &mut Context) -> RLResult {
+    Arity::Range(2, 3).check("if", args.len())?;
+
+    let has_else = args.len() == 3;
     let condition = args.remove(0);
 
-    match eval(condition, env) {
-        Ok(RLType::True) => eval(args.remove(0), env),
-        Ok(RLType::False) if has_else => eval(args.remove(1), env),
+    match eval(condition, env, ctx) {
+        Ok(RLType::True) => eval(args.remove(0), env, ctx),
+        Ok(RLType::False) if has_else => eval(args.remove(1), env, ctx),
         Ok(_) => Ok(RLType::Nil),
         Err(e) => Err(e),
     }
 }
 
 
-// Parses the input &str and evals, for use in the REPL
-pub fn parse_and_eval(input: &str, env: &Env) -> RLResult {
+// Parses the input &str and evals the first form in it
+pub fn parse_and_eval(input: &str, env: &Env, ctx: &mut Context) -> RLResult {
     let mut tokens = tokenize(input);
-    let tree = parse_form(&mut tokens);
+    let tree = parse_form(&mut tokens)?;
 
-    match tree {
-        Ok(cell) => eval(cell, env),
-        Err(_) => Ok(RLType::Symbol("parse error.".to_string())),
-    }
+    eval(tree, env, ctx)
 }
 
 // evaluates a vector of values so that they are all atomic
-fn make_atomic(tokens: Vec<RLType>, env: &Env) -> RLResult {
+fn eval_args(tokens: Vec<RLType>, env: &Env, ctx: &mut Context) -> Result<Vec<RLType>, RLError> {
     let mut args: Vec<RLType> = Vec::new();
     for arg in tokens {
-        let value = try!(eval(arg, env));
+        let value = eval(arg, env, ctx)?;
         args.push(value);
     }
-    Ok(RLType::List(args))
+    Ok(args)
 }
 
 // TODO: move tests in separate file
 #[test]
 fn eval_returns_pi() {
     let t = RLType::Symbol("pi".to_string());
-    let mut env = Environment::default();
+    let env = Environment::default();
+    let mut ctx = Context::new();
 
-    let res = match eval(t, &mut env) {
-        RLType::Number(n) => n,
+    let res = match eval(t, &env, &mut ctx) {
+        Ok(RLType::Number(n)) => n,
         _ => 0.0,
     };
 
-    assert_eq!(res, 3.14159265);
+    assert_eq!(res, ::std::f64::consts::PI);
 }
 
 
@@ -218,10 +235,11 @@ fn eval_sum() {
     let args = vec![RLType::Symbol("+".to_string()), RLType::Number(2.0), RLType::Number(1.0)];
 
     let t = RLType::List(args);
-    let mut env = Environment::default();
+    let env = Environment::default();
+    let mut ctx = Context::new();
 
-    let res = match eval(t, &mut env) {
-        RLType::Number(n) => n,
+    let res = match eval(t, &env, &mut ctx) {
+        Ok(RLType::Number(n)) => n,
         _ => 0.0,
     };
 
@@ -233,10 +251,11 @@ fn eval_sum() {
                     RLType::Number(1.0)];
 
     let t = RLType::List(args);
-    let mut env = Environment::default();
+    let env = Environment::default();
+    let mut ctx = Context::new();
 
-    let res = match eval(t, &mut env) {
-        RLType::Number(n) => n,
+    let res = match eval(t, &env, &mut ctx) {
+        Ok(RLType::Number(n)) => n,
         _ => 0.0,
     };
 
@@ -252,12 +271,112 @@ fn eval_sub() {
                     RLType::Number(1.0)];
 
     let t = RLType::List(args);
-    let mut env = Environment::default();
+    let env = Environment::default();
+    let mut ctx = Context::new();
 
-    let res = match eval(t, &mut env) {
-        RLType::Number(n) => n,
+    let res = match eval(t, &env, &mut ctx) {
+        Ok(RLType::Number(n)) => n,
         _ => -10.0,
     };
 
     assert_eq!(res, 0.0);
 }
+
+
+#[test]
+fn eval_stops_when_cancelled() {
+    use lisp::context::CancelToken;
+
+    let env = Environment::default();
+    let mut ctx = Context::new();
+    let token = CancelToken::new();
+    ctx.set_cancel_token(token.clone());
+
+    token.cancel();
+    match parse_and_eval("(+ 1 2)", &env, &mut ctx) {
+        Err(ref e) if matches!(*e.kind(), ErrorKind::Cancelled) => (),
+        _ => panic!("evaluation was not cancelled"),
+    }
+
+    // a cancelled def! doesn't bind anything
+    let _ = parse_and_eval("(def! x 1)", &env, &mut ctx);
+    assert!(env.borrow().lookup("x").is_err());
+
+    token.reset();
+    match parse_and_eval("(+ 1 2)", &env, &mut ctx) {
+        Ok(RLType::Number(n)) => assert_eq!(n, 3.0),
+        _ => panic!("evaluation failed after reset"),
+    }
+}
+
+#[test]
+fn eval_can_be_cancelled_from_another_thread() {
+    use std::thread;
+    use std::time::Duration;
+
+    let env = Environment::default();
+    let mut ctx = Context::new();
+    let token = ctx.cancel_token();
+
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        token.cancel();
+    });
+
+    let fibo = "(def! fibo (lambda (n) (if (<= n 2) n (+ (fibo (- n 1)) (fibo (- n 2))))))";
+    assert!(parse_and_eval(fibo, &env, &mut ctx).is_ok());
+
+    match parse_and_eval("(fibo 60)", &env, &mut ctx) {
+        Err(ref e) if matches!(*e.kind(), ErrorKind::Cancelled) => (),
+        _ => panic!("evaluation was not cancelled"),
+    }
+    canceller.join().unwrap();
+}
+
+#[test]
+fn eval_stops_after_deadline() {
+    use std::time::{Duration, Instant};
+
+    let env = Environment::default();
+    let mut ctx = Context::new();
+
+    let fibo = "(def! fibo (lambda (n) (if (<= n 2) n (+ (fibo (- n 1)) (fibo (- n 2))))))";
+    assert!(parse_and_eval(fibo, &env, &mut ctx).is_ok());
+
+    ctx.set_timeout(Duration::from_millis(20));
+    let start = Instant::now();
+    match parse_and_eval("(fibo 60)", &env, &mut ctx) {
+        Err(ref e) if matches!(*e.kind(), ErrorKind::DeadlineExceeded) => (),
+        _ => panic!("evaluation didn't stop at the deadline"),
+    }
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // the environment is still usable once the deadline is removed
+    ctx.clear_deadline();
+    match parse_and_eval("(fibo 5)", &env, &mut ctx) {
+        Ok(RLType::Number(n)) => assert_eq!(n, 8.0),
+        _ => panic!("evaluation failed after clearing the deadline"),
+    }
+}
+
+#[test]
+fn eval_reports_arity_of_special_forms() {
+    let env = Environment::default();
+    let mut ctx = Context::new();
+
+    let forms = &[
+        ("(def!)", "def!"), ("(def! x)", "def!"), ("(def! x 1 2)", "def!"),
+        ("(if)", "if"), ("(if #t)", "if"), ("(if #t 1 2 3)", "if"),
+        ("(do)", "do"), ("(lambda)", "lambda"),
+    ];
+    for &(code, form) in forms {
+        match parse_and_eval(code, &env, &mut ctx) {
+            Err(ref e) => match *e.kind() {
+                ErrorKind::Arity { ref name, .. } => assert_eq!(name, form, "{}", code),
+                ref other => panic!("{}: got {:?}", code, other),
+            },
+            Ok(value) => panic!("{}: got {:?}", code, value),
+        }
+    }
+    assert!(env.borrow().lookup("x").is_err());
+}
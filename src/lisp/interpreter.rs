@@ -0,0 +1,299 @@
+use std::fs;
+use std::path::Path;
+
+use lisp::context::Context;
+use lisp::convert::IntoNative;
+use lisp::env::{Env, Environment};
+use lisp::eval::{eval, apply};
+use lisp::lex::parse_all;
+use lisp::types::*;
+
+// An Interpreter owns a root Environment and the Context used to evaluate code in it.
+// It is the entry point for applications embedding the language.
+pub struct Interpreter {
+    env: Env,
+    ctx: Context,
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    // Creates an interpreter with the core functions available
+    pub fn new() -> Interpreter {
+        Interpreter::with_env(Environment::default())
+    }
+
+    // Creates an interpreter that evaluates in the provided environment
+    pub fn with_env(env: Env) -> Interpreter {
+        Interpreter {
+            env,
+            ctx: Context::new(),
+        }
+    }
+
+    // The root environment, bindings inserted here are visible to all evaluated code
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    // Replaces the root environment with a new default one, dropping every binding
+    pub fn reset(&mut self) {
+        self.env = Environment::default();
+    }
+
+    // The context, used to set cancellation tokens and deadlines
+    pub fn context(&mut self) -> &mut Context {
+        &mut self.ctx
+    }
+
+    // Parses the input and evaluates each form in it in the root environment.
+    // Returns the value of the last form, or nil if there are none.
+    pub fn eval_str(&mut self, input: &str) -> RLResult {
+        let mut result = RLType::Nil;
+        for form in parse_all(input)? {
+            result = self.eval_value(form)?;
+        }
+        Ok(result)
+    }
+
+    // Reads a whole file and evaluates it, a first line starting with #! is ignored
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> RLResult {
+        let path = path.as_ref();
+        let mut source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => return error(format!("Cannot read {}: {}", path.display(), e)),
+        };
+
+        if source.starts_with("#!") {
+            // keep the newline so that lines in errors are still correct
+            let end = source.find('\n').unwrap_or(source.len());
+            source.replace_range(..end, "");
+        }
+        self.eval_str(&source)
+    }
+
+    // Evaluates an already parsed value in the root environment
+    pub fn eval_value(&mut self, value: RLType) -> RLResult {
+        eval(value, &self.env, &mut self.ctx)
+    }
+
+    // Calls the function bound to name in the root environment with the provided arguments,
+    // which are not evaluated again
+    pub fn call(&mut self, name: &str, args: &[RLType]) -> RLResult {
+        let func = self.env.borrow().lookup(name)?;
+        self.call_value(&func, args)
+    }
+
+    // Calls a function value, such as a lambda returned by an evaluation
+    pub fn call_value(&mut self, func: &RLType, args: &[RLType]) -> RLResult {
+        apply(func, args.to_vec(), &self.env, &mut self.ctx)
+    }
+
+    // Binds a value to name in the root environment
+    pub fn define(&mut self, name: &str, value: RLType) {
+        self.env.borrow_mut().insert(name.to_string(), value);
+    }
+
+    // Binds a builtin function to name in the root environment
+    pub fn register_fn(&mut self, name: &str, func: fn(Vec<RLType>) -> RLResult) {
+        self.env.borrow_mut().insert(name.to_string(), RLType::Proc(func));
+    }
+
+    // Binds a Rust closure to name in the root environment, the closure can capture
+    // state from the host application
+    pub fn register_native<F>(&mut self, name: &str, arity: Arity, func: F)
+        where F: Fn(&mut Context, Vec<RLType>) -> RLResult + 'static
+    {
+        self.env.borrow_mut().insert_native(name, arity, func);
+    }
+
+    // Binds a plain Rust function to name in the root environment, such as fn(f64, f64) -> f64.
+    // Arity and argument types are checked automatically.
+    pub fn register_typed<F, Args>(&mut self, name: &str, func: F)
+        where F: IntoNative<Args>
+    {
+        self.env.borrow_mut().insert_typed(name, func);
+    }
+}
+
+#[test]
+fn interpreter_evaluates_strings() {
+    let mut interp = Interpreter::new();
+
+    assert!(interp.eval_str("(def! answer (* 21 2))").is_ok());
+    match interp.eval_str("answer") {
+        Ok(RLType::Number(n)) => assert_eq!(n, 42.0),
+        _ => panic!("answer is not bound"),
+    }
+}
+
+#[test]
+fn interpreter_evaluates_values() {
+    let mut interp = Interpreter::new();
+    let sum = RLType::List(vec![RLType::Symbol("+".to_string()),
+                                RLType::Number(1.0),
+                                RLType::Number(2.0)]);
+
+    match interp.eval_value(sum) {
+        Ok(RLType::Number(n)) => assert_eq!(n, 3.0),
+        _ => panic!("wrong result"),
+    }
+}
+
+#[test]
+fn interpreter_registers_functions() {
+    fn count(args: Vec<RLType>) -> RLResult {
+        Ok(RLType::Number(args.len() as f64))
+    }
+
+    let mut interp = Interpreter::new();
+    interp.register_fn("count", count);
+
+    match interp.eval_str("(count 1 2 3)") {
+        Ok(RLType::Number(n)) => assert_eq!(n, 3.0),
+        _ => panic!("count is not registered"),
+    }
+}
+
+#[test]
+fn interpreter_registers_closures() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let counter = Rc::new(Cell::new(0));
+    let captured = counter.clone();
+
+    let mut interp = Interpreter::new();
+    interp.register_native("tick", Arity::Range(0, 1), move |_, args| {
+        let step = match args.first() {
+            Some(&RLType::Number(n)) => n as u32,
+            Some(other) => return error(format!("Not a number: {:?}", other)),
+            None => 1,
+        };
+        captured.set(captured.get() + step);
+        Ok(RLType::Number(captured.get() as f64))
+    });
+
+    assert!(interp.eval_str("(tick)").is_ok());
+    match interp.eval_str("(tick 10)") {
+        Ok(RLType::Number(n)) => assert_eq!(n, 11.0),
+        _ => panic!("tick is not registered"),
+    }
+    assert_eq!(counter.get(), 11);
+
+    // arity is checked before calling the closure
+    assert!(interp.eval_str("(tick 1 2)").is_err());
+    assert_eq!(counter.get(), 11);
+}
+
+#[test]
+fn interpreter_registers_typed_functions() {
+    let mut interp = Interpreter::new();
+    interp.register_typed("hypot", |a: f64, b: f64| (a * a + b * b).sqrt());
+    interp.register_typed("greet", |name: String| format!("Hello {}", name));
+
+    match interp.eval_str("(hypot 3 4)") {
+        Ok(RLType::Number(n)) => assert_eq!(n, 5.0),
+        _ => panic!("hypot is not registered"),
+    }
+    match interp.eval_str("(greet \"world\")") {
+        Ok(RLType::Str(s)) => assert_eq!(s, "Hello world"),
+        _ => panic!("greet is not registered"),
+    }
+    match interp.eval_str("(greet 1)") {
+        Err(e) => assert_eq!(e.get_message(), "greet: argument 1: expected string, got number 1"),
+        Ok(_) => panic!("greet takes a string"),
+    }
+}
+
+#[test]
+fn interpreter_calls_lisp_functions() {
+    let mut interp = Interpreter::new();
+    assert!(interp.eval_str("(def! on-event (lambda (name data) (list name data)))").is_ok());
+
+    // arguments are passed as values, the list is not evaluated as a call
+    let data = RLType::List(vec![RLType::Number(1.0), RLType::Number(2.0)]);
+    match interp.call("on-event", &[RLType::Str("click".to_string()), data]) {
+        Ok(RLType::List(ref values)) => {
+            assert_eq!(values.len(), 2);
+            assert_eq!(format!("{}", values[1]), "(1 2)");
+        },
+        _ => panic!("on-event failed"),
+    }
+
+    match interp.call("+", &[RLType::Number(1.0), RLType::Number(2.0)]) {
+        Ok(RLType::Number(n)) => assert_eq!(n, 3.0),
+        _ => panic!("+ failed"),
+    }
+
+    assert!(interp.call("on-event", &[RLType::Nil]).is_err());
+    assert!(interp.call("pi", &[]).is_err());
+    assert!(interp.call("undefined", &[]).is_err());
+}
+
+#[test]
+fn interpreter_natives_call_back_lisp_functions() {
+    let mut interp = Interpreter::new();
+    interp.register_native("twice", Arity::Exact(2), |ctx, args| {
+        let once = ctx.call(&args[0], vec![args[1].clone()])?;
+        ctx.call(&args[0], vec![once])
+    });
+
+    match interp.eval_str("(twice (lambda (x) (* x 3)) 2)") {
+        Ok(RLType::Number(n)) => assert_eq!(n, 18.0),
+        _ => panic!("twice failed"),
+    }
+}
+
+#[test]
+fn interpreter_reports_parse_errors() {
+    let mut interp = Interpreter::new();
+
+    assert!(interp.eval_str("(+ 1 2").is_err());
+    assert!(interp.eval_str(")").is_err());
+}
+
+#[test]
+fn interpreter_evaluates_every_form() {
+    let mut interp = Interpreter::new();
+
+    match interp.eval_str("(def! x 20) (def! y (+ x 1)) (* y 2)") {
+        Ok(RLType::Number(n)) => assert_eq!(n, 42.0),
+        _ => panic!("wrong result"),
+    }
+    assert!(matches!(interp.eval_str("; only a comment"), Ok(RLType::Nil)));
+
+    // forms before an error are evaluated
+    assert!(interp.eval_str("(def! z 1) (undefined) (def! w 2)").is_err());
+    assert!(interp.eval_str("z").is_ok());
+    assert!(interp.eval_str("w").is_err());
+}
+
+#[test]
+fn interpreter_loads_files() {
+    use std::env;
+    use std::fs::File;
+    use std::io::Write;
+
+    let path = env::temp_dir().join(format!("rustl-load-{}.lisp", ::std::process::id()));
+    {
+        let mut file = File::create(&path).unwrap();
+        writeln!(file, "#!/usr/bin/env lisp").unwrap();
+        writeln!(file, "(def! greet (lambda (who) who))").unwrap();
+        writeln!(file, "(greet \"all\")").unwrap();
+    }
+
+    let mut interp = Interpreter::new();
+    let result = interp.load_file(&path);
+    fs::remove_file(&path).unwrap();
+
+    match result {
+        Ok(RLType::Str(ref s)) => assert_eq!(s, "all"),
+        _ => panic!("the file was not evaluated"),
+    }
+    assert!(interp.load_file(&path).is_err());
+}
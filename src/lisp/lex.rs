@@ -1,75 +1,313 @@
+use std::collections::VecDeque;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use lisp::modules::{atoms, format, maps, sets, vectors};
 use lisp::types::*;
 
 // TODO: documentation
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ParseError {
     UnbalancedParens,
     EOFReached,
+    UnterminatedString,
+    // An interpolated string with ${} not holding exactly one expression
+    InvalidInterpolation,
+    // A char literal with an unknown name or code point
+    InvalidChar,
+    // A map literal with a key and no value
+    MissingMapValue,
+}
+
+// The position of a token in the input: byte offsets, plus line and column (from 1) of the start
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+pub struct Token {
+    pub text: String,
+    pub span: Span,
+}
+
+pub type ParseResult = Result<RLType, RLError>;
+
+fn parse_error(error: ParseError, span: Span) -> ParseResult {
+    Err(RLError::new(ErrorKind::Parse(error, span)))
 }
 
-pub type ParseResult = Result<RLType, ParseError>;
+// Produces a queue of Tokens, with no empty ones.
+// Parens, brackets, braces, #{ and @ are tokens on their own, string literals are kept whole
+// with their quotes (and the # of interpolated strings), comments start with ; and run until
+// the end of the line.
+pub fn tokenize(input: &str) -> VecDeque<Token> {
+    let mut tokens = VecDeque::new();
+    let mut chars = input.char_indices().peekable();
+    let (mut line, mut column) = (1, 0);
+
+    while let Some((start, c)) = chars.next() {
+        let mut span = Span { start, end: start + c.len_utf8(), line, column: column + 1 };
+        let mut text = c.to_string();
 
-// Produces a vector of Strings, with no empty ones
-pub fn tokenize(input: &str) -> Vec<String> {
-    let formatted = format_braces(input);
-    let mut tokens = Vec::new();
+        match c {
+            '\n' => {
+                line += 1;
+                column = 0;
+                continue;
+            },
+            _ if c.is_whitespace() => {
+                column += 1;
+                continue;
+            },
+            ';' => {
+                column += 1;
+                while let Some(&(_, c)) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    column += 1;
+                    chars.next();
+                }
+                continue;
+            },
+            '(' | ')' | '[' | ']' | '{' | '}' | '@' => (),
+            '"' => line += read_string(&mut chars, &mut text),
+            '#' if chars.peek().map(|&(_, c)| c) == Some('"') => {
+                text.push('"');
+                chars.next();
+                line += read_interpolated_string(&mut chars, &mut text);
+            },
+            '#' if chars.peek().map(|&(_, c)| c) == Some('{') => {
+                text.push('{');
+                chars.next();
+            },
+            '#' if chars.peek().map(|&(_, c)| c) == Some('\\') => {
+                // a char literal, the char after #\ is part of it even if it is a delimiter
+                text.push('\\');
+                chars.next();
+                if let Some((_, c)) = chars.next() {
+                    text.push(c);
+                    if c == '\n' {
+                        line += 1;
+                    }
+                }
+                read_symbol(&mut chars, &mut text);
+            },
+            _ => read_symbol(&mut chars, &mut text),
+        }
 
-    for token in formatted.split(' ') {
-        let t = token.trim();
-        // push only tokens with text
-        if t != "" {
-            tokens.push(t.to_string());
+        span.end = start + text.len();
+        if span.line == line {
+            column += text.chars().count();
+        } else {
+            column = text.rsplit('\n').next().map_or(0, |last| last.chars().count());
         }
+        tokens.push_back(Token { text, span });
     }
     tokens
 }
 
-// Puts spaces befor and after braces
-fn format_braces(input: &str) -> String {
-    input.replace("(", " ( ").replace(")", " ) ").trim().to_string()
+// Adds the rest of a symbol or a number to text, up to a delimiter
+fn read_symbol(chars: &mut Peekable<CharIndices>, text: &mut String) {
+    while let Some(&(_, c)) = chars.peek() {
+        if c.is_whitespace() || "()[]{}\";".contains(c) {
+            break;
+        }
+        text.push(c);
+        chars.next();
+    }
+}
+
+// Adds the rest of a string literal to text, up to the closing quote.
+// Returns the number of lines in the literal.
+fn read_string(chars: &mut Peekable<CharIndices>, text: &mut String) -> usize {
+    let mut lines = 0;
+    let mut escaped = false;
+    for (_, c) in chars.by_ref() {
+        text.push(c);
+        if c == '\n' {
+            lines += 1;
+        }
+        match c {
+            '"' if !escaped => break,
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    lines
+}
+
+// Like read_string, for an interpolated string where the expressions in ${} can contain quotes
+fn read_interpolated_string(chars: &mut Peekable<CharIndices>, text: &mut String) -> usize {
+    let mut lines = 0;
+    // the nesting of braces, 0 outside of ${}
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    while let Some((_, c)) = chars.next() {
+        text.push(c);
+        if c == '\n' {
+            lines += 1;
+        }
+
+        if depth == 0 || in_string {
+            // whether c follows a backslash, escaped is then updated for the next char
+            let after_backslash = escaped;
+            let closing = !after_backslash && c == '"';
+            escaped = !after_backslash && c == '\\';
+            if closing && depth == 0 {
+                break;
+            } else if closing {
+                in_string = false;
+            } else if depth == 0 && !after_backslash && c == '$'
+                && chars.peek().map(|&(_, c)| c) == Some('{') {
+                text.push('{');
+                chars.next();
+                depth = 1;
+            }
+        } else {
+            match c {
+                '"' => in_string = true,
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => (),
+            }
+        }
+    }
+    lines
+}
+
+// Parses every form in the input, in order
+pub fn parse_all(input: &str) -> Result<Vec<RLType>, RLError> {
+    let mut tokens = tokenize(input);
+    let mut forms = Vec::new();
+
+    while !tokens.is_empty() {
+        forms.push(parse_form(&mut tokens)?);
+    }
+    Ok(forms)
 }
 
-pub fn parse_form(tokens: &mut Vec<String>) -> ParseResult {
+pub fn parse_form(tokens: &mut VecDeque<Token>) -> ParseResult {
     if tokens.is_empty() {
-        return Err(ParseError::EOFReached);
+        return parse_error(ParseError::EOFReached, Span::default());
     }
 
-    match tokens[0].as_ref() {
-        "(" => parse_list(tokens),
-        ")" => Err(ParseError::UnbalancedParens),
+    match tokens[0].text.as_ref() {
+        "(" => parse_list(tokens, ")").map(RLType::List),
+        "[" => parse_vector(tokens),
+        "{" => parse_map(tokens),
+        "#{" => parse_set(tokens),
+        "@" => parse_deref(tokens),
+        ")" | "]" | "}" => parse_error(ParseError::UnbalancedParens, tokens[0].span),
         _ => parse_atom(tokens),
     }
 }
 
-fn parse_list(tokens: &mut Vec<String>) -> ParseResult {
+// Reads the forms up to the close token, which must match the opening one
+fn parse_list(tokens: &mut VecDeque<Token>, close: &str) -> Result<Vec<RLType>, RLError> {
     let mut list: Vec<RLType> = Vec::new();
-    // discard first '(', this fails of course
-    tokens.remove(0);
+    // discard the opening paren or brace
+    let open = tokens.pop_front().unwrap();
 
-    while !tokens.is_empty() && &tokens[0] != ")" {
-        let cell = try!(parse_form(tokens));
+    while !tokens.is_empty() && !is_closing(&tokens[0].text) {
+        let cell = parse_form(tokens)?;
         list.push(cell);
     }
 
+    // the list is not closed, the input ended too early
+    let closing = match tokens.pop_front() {
+        Some(closing) => closing,
+        None => return Err(RLError::new(ErrorKind::Parse(ParseError::EOFReached, open.span))),
+    };
+    if closing.text != close {
+        return Err(RLError::new(ErrorKind::Parse(ParseError::UnbalancedParens, closing.span)));
+    }
+    Ok(list)
+}
+
+fn is_closing(text: &str) -> bool {
+    text == ")" || text == "]" || text == "}"
+}
+
+// A vector literal [a b] is read as (vector a b), so that its elements are evaluated. The call
+// holds the builtin itself.
+fn parse_vector(tokens: &mut VecDeque<Token>) -> ParseResult {
+    let mut forms = parse_list(tokens, "]")?;
+    forms.insert(0, RLType::Proc(vectors::vector));
+    Ok(RLType::List(forms))
+}
+
+// @form is read as (deref form), with the builtin itself in the call
+fn parse_deref(tokens: &mut VecDeque<Token>) -> ParseResult {
+    let at = tokens.pop_front().unwrap();
     if tokens.is_empty() {
-        return Err(ParseError::UnbalancedParens);
+        return parse_error(ParseError::EOFReached, at.span);
+    }
+
+    let form = parse_form(tokens)?;
+    Ok(RLType::List(vec![RLType::Proc(atoms::deref), form]))
+}
+
+// A set literal #{a b} is read as (hash-set a b), so that its elements are evaluated. The call
+// holds the builtin itself.
+fn parse_set(tokens: &mut VecDeque<Token>) -> ParseResult {
+    let mut forms = parse_list(tokens, "}")?;
+    forms.insert(0, RLType::Proc(sets::hash_set));
+    Ok(RLType::List(forms))
+}
+
+// A map literal {k1 v1 k2 v2} is read as (hash-map k1 v1 k2 v2), so that its keys and values
+// are evaluated. Like for interpolated strings, the call holds the builtin itself.
+fn parse_map(tokens: &mut VecDeque<Token>) -> ParseResult {
+    let span = tokens[0].span;
+    let mut forms = parse_list(tokens, "}")?;
+    if !forms.len().is_multiple_of(2) {
+        return parse_error(ParseError::MissingMapValue, span);
     }
 
-    tokens.remove(0);
-    Ok(RLType::List(list))
+    forms.insert(0, RLType::Proc(maps::hash_map));
+    Ok(RLType::List(forms))
 }
 
-fn parse_atom(tokens: &mut Vec<String>) -> ParseResult {
-    let token = tokens.remove(0);
+fn parse_atom(tokens: &mut VecDeque<Token>) -> ParseResult {
+    let token = tokens.pop_front().unwrap();
+
+    if token.text.starts_with('"') {
+        return parse_string(&token);
+    }
+    if token.text.starts_with("#\"") {
+        return parse_interpolated(&token);
+    }
+    if token.text.starts_with("#\\") {
+        return parse_char(&token);
+    }
 
-    match parse_number(&token) {
+    match parse_number(&token.text) {
         Some(cell) => Ok(cell),
-        None => Ok(parse_other_values(&token)),
+        None => Ok(parse_other_values(&token.text)),
     }
 }
 
-fn parse_number(text: &str) -> Option<RLType> {
-    let n_f = text.parse::<f32>();
+// Reads decimal numbers and the special values +inf.0, -inf.0 and +nan.0. Words such as inf
+// or NaN, which Rust would parse as numbers, are left to be symbols.
+pub fn parse_number(text: &str) -> Option<RLType> {
+    match text {
+        "+inf.0" => return Some(RLType::Number(f64::INFINITY)),
+        "-inf.0" => return Some(RLType::Number(f64::NEG_INFINITY)),
+        "+nan.0" | "-nan.0" => return Some(RLType::Number(f64::NAN)),
+        _ => (),
+    }
+    if text.chars().any(|c| c.is_alphabetic() && c != 'e' && c != 'E') {
+        return None;
+    }
+
+    let n_f = text.parse::<f64>();
 
     match n_f {
         Ok(number) => Some(RLType::Number(number)),
@@ -77,11 +315,134 @@ fn parse_number(text: &str) -> Option<RLType> {
     }
 }
 
+// Reads a string literal token, including its quotes, replacing escape sequences
+fn parse_string(token: &Token) -> ParseResult {
+    let mut text = String::new();
+    let mut chars = token.text.chars().skip(1);
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Ok(RLType::Str(text)),
+            '\\' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some('r') => text.push('\r'),
+                Some('0') => text.push('\0'),
+                Some(other) => text.push(other),
+                None => break,
+            },
+            _ => text.push(c),
+        }
+    }
+
+    parse_error(ParseError::UnterminatedString, token.span)
+}
+
+// Reads an interpolated string such as #"${n} items", which is expanded to a call to format
+// with the expressions between ${ and } as arguments: (format "~a items" n). The call holds
+// the builtin itself rather than its name, so that rebinding format doesn't break it.
+// A $ not followed by { is kept as is, \$ can be used to write ${ literally.
+fn parse_interpolated(token: &Token) -> ParseResult {
+    let mut template = String::new();
+    let mut call = vec![RLType::Proc(format::format), RLType::Nil];
+    let mut chars = token.text.chars().skip(2).peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                call[1] = RLType::Str(template);
+                return Ok(RLType::List(call));
+            },
+            '\\' => match chars.next() {
+                Some('n') => template.push('\n'),
+                Some('t') => template.push('\t'),
+                Some('r') => template.push('\r'),
+                Some('0') => template.push('\0'),
+                Some('~') => template.push_str("~~"),
+                Some(other) => template.push(other),
+                None => break,
+            },
+            '~' => template.push_str("~~"),
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let source = read_interpolation(&mut chars);
+                let forms = match source {
+                    Some(ref source) => parse_all(source),
+                    None => break,
+                };
+                match forms {
+                    Ok(mut forms) if forms.len() == 1 => call.push(forms.remove(0)),
+                    _ => return parse_error(ParseError::InvalidInterpolation, token.span),
+                }
+                template.push_str("~a");
+            },
+            _ => template.push(c),
+        }
+    }
+
+    parse_error(ParseError::UnterminatedString, token.span)
+}
+
+// The source of an interpolated expression, up to the } closing it. Braces in string literals
+// are skipped. Returns None if the string ends first.
+fn read_interpolation<I: Iterator<Item = char>>(chars: &mut I) -> Option<String> {
+    let mut source = String::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in chars {
+        if in_string {
+            in_string = escaped || c != '"';
+            escaped = !escaped && c == '\\';
+        } else {
+            match c {
+                '"' => in_string = true,
+                '{' => depth += 1,
+                '}' if depth == 0 => return Some(source),
+                '}' => depth -= 1,
+                _ => (),
+            }
+        }
+        source.push(c);
+    }
+    None
+}
+
+// Names of chars in literals such as #\space, the printer uses them too
+pub const CHAR_NAMES: &[(&str, char)] = &[
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("nul", '\0'),
+];
+
+// Reads a char literal: #\a, a name such as #\space or a code point in hex such as #\x41
+fn parse_char(token: &Token) -> ParseResult {
+    let literal = &token.text[2..];
+    let mut chars = literal.chars();
+
+    let c = match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        (Some('x'), Some(_)) => {
+            u32::from_str_radix(&literal[1..], 16).ok().and_then(::std::char::from_u32)
+        },
+        _ => CHAR_NAMES.iter().find(|&&(name, _)| name == literal).map(|&(_, c)| c),
+    };
+
+    match c {
+        Some(c) => Ok(RLType::Char(c)),
+        None => parse_error(ParseError::InvalidChar, token.span),
+    }
+}
+
 fn parse_other_values(text: &str) -> RLType {
     match text {
         "#t" => RLType::True,
         "#f" => RLType::False,
         "nil" => RLType::Nil,
+        _ if text.len() > 1 && text.starts_with(':') => RLType::Keyword(Keyword::new(&text[1..])),
         _ => RLType::Symbol(text.to_string()),
     }
 }
@@ -112,3 +473,91 @@ fn parse_other_values(text: &str) -> RLType {
 //
 //     assert_eq!(&token, atom);
 // }
+
+#[test]
+fn parse_string_literals() {
+    let mut tokens = tokenize("(greet \"hello (big) \\\"world\\\"\")");
+    assert_eq!(tokens.len(), 4);
+
+    match parse_form(&mut tokens) {
+        Ok(RLType::List(ref values)) => match values[1] {
+            RLType::Str(ref text) => assert_eq!(text, "hello (big) \"world\""),
+            _ => panic!("not a string"),
+        },
+        _ => panic!("not a list"),
+    }
+
+    let mut tokens = tokenize("\"unterminated");
+    assert!(parse_form(&mut tokens).is_err());
+}
+
+#[test]
+fn parse_all_forms_and_comments() {
+    let input = "; a script\n(def! x 1) ; binds x\n\"a ; b\"\nx;end";
+
+    match parse_all(input) {
+        Ok(forms) => {
+            assert_eq!(forms.len(), 3);
+            match forms[1] {
+                RLType::Str(ref text) => assert_eq!(text, "a ; b"),
+                _ => panic!("not a string"),
+            }
+            match forms[2] {
+                RLType::Symbol(ref name) => assert_eq!(name, "x"),
+                _ => panic!("not a symbol"),
+            }
+        },
+        Err(_) => panic!("parse failed"),
+    }
+
+    assert!(parse_all("(def! x 1) (+ x").is_err());
+    assert_eq!(parse_all("  ; nothing here").ok().map(|forms| forms.len()), Some(0));
+}
+
+#[test]
+fn parse_special_numbers() {
+    let forms = parse_all("+inf.0 -inf.0 +nan.0 inf nan NaN infinity 1e3 -2.5").unwrap();
+
+    assert_eq!(forms[0], RLType::Number(f64::INFINITY));
+    assert_eq!(forms[1], RLType::Number(f64::NEG_INFINITY));
+    match forms[2] {
+        RLType::Number(n) => assert!(n.is_nan()),
+        _ => panic!("not a number"),
+    }
+    for form in &forms[3..7] {
+        match *form {
+            RLType::Symbol(_) => (),
+            _ => panic!("{:?} is not a symbol", form),
+        }
+    }
+    assert_eq!(forms[7], RLType::Number(1000.0));
+    assert_eq!(forms[8], RLType::Number(-2.5));
+}
+
+#[test]
+fn parse_interpolated_strings() {
+    // the arguments of the call to the format builtin
+    let arguments = |form: &RLType| match *form {
+        RLType::List(ref call) if call[0] == RLType::Proc(format::format) => {
+            format!("{:?}", RLType::List(call[1..].to_vec()))
+        },
+        _ => panic!("{:?} is not a call to format", form),
+    };
+
+    let forms = parse_all("#\"${n} items of ${(str \"a}\" x)} at 100~ $5\" x").unwrap();
+    assert_eq!(forms.len(), 2);
+    assert_eq!(arguments(&forms[0]), "(\"~a items of ~a at 100~~ $5\" n (str \"a}\" x))");
+
+    let forms = parse_all("#\"line\\n\\${x}\"").unwrap();
+    assert_eq!(arguments(&forms[0]), "(\"line\\n${x}\")");
+
+    for input in &["#\"${}\"", "#\"${a b}\"", "#\"${(a}\""] {
+        let error = parse_all(input).unwrap_err();
+        match *error.kind() {
+            ErrorKind::Parse(ParseError::InvalidInterpolation, _) => (),
+            _ => panic!("{} gave {}", input, error),
+        }
+    }
+    assert!(parse_all("#\"${x").unwrap_err().is_incomplete_input());
+    assert!(parse_all("#\"abc").unwrap_err().is_incomplete_input());
+}
@@ -1,7 +1,16 @@
 #![allow(dead_code)]
+// Map keys are RLTypes, which can hold atoms. Atoms hash and compare by identity, so changing
+// their value doesn't move them in a map.
+#![allow(clippy::mutable_key_type)]
 
+pub mod context;
+pub mod convert;
 pub mod env;
+pub mod error;
 pub mod lex;
 pub mod eval;
+pub mod interpreter;
+pub mod printer;
 pub mod types;
 pub mod modules;
+pub mod persistent;
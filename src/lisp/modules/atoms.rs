@@ -0,0 +1,122 @@
+// This module contains atoms, cells holding a value that can be replaced. Every value holding
+// an atom sees the change, which makes them the way to keep state without redefining a name.
+// Boxes are the same cells under the Scheme names box, unbox and set-box!.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use lisp::context::Context;
+use lisp::convert::IntoLisp;
+use lisp::env::Env;
+use lisp::types::*;
+
+// Adds this module's functions to the provided environment
+pub fn add_module(env: &mut Env) {
+    let functions: &[(&str, Builtin)] = &[
+        ("atom", atom),
+        ("box", atom),
+        ("atom?", is_atom),
+        ("deref", deref),
+        ("unbox", deref),
+        ("reset!", reset),
+        ("set-box!", reset),
+    ];
+    for &(name, function) in functions {
+        env.borrow_mut().insert(name.to_string(), RLType::Proc(function));
+    }
+    env.borrow_mut().insert_native("swap!", Arity::AtLeast(2), swap);
+}
+
+fn cell(arg: &RLType) -> Result<&Rc<RefCell<RLType>>, RLError> {
+    match *arg {
+        RLType::Atom(ref cell) => Ok(cell),
+        _ => Err(RLError::type_mismatch("atom", arg)),
+    }
+}
+
+fn atom(mut args: Vec<RLType>) -> RLResult {
+    match args.len() {
+        1 => Ok(RLType::Atom(Rc::new(RefCell::new(args.remove(0))))),
+        count => Err(RLError::arity("atom", Arity::Exact(1), count)),
+    }
+}
+
+fn is_atom(args: Vec<RLType>) -> RLResult {
+    match *args {
+        [ref value] => Ok(matches!(*value, RLType::Atom(_)).into_lisp()),
+        _ => Err(RLError::arity("atom?", Arity::Exact(1), args.len())),
+    }
+}
+
+// Also called by the @ shorthand of the reader
+pub fn deref(args: Vec<RLType>) -> RLResult {
+    match *args {
+        [ref arg] => Ok(cell(arg)?.borrow().clone()),
+        _ => Err(RLError::arity("deref", Arity::Exact(1), args.len())),
+    }
+}
+
+// Replaces the value of an atom, returns the new value
+fn reset(mut args: Vec<RLType>) -> RLResult {
+    Arity::Exact(2).check("reset!", args.len())?;
+    let value = args.pop().unwrap();
+    *cell(&args[0])?.borrow_mut() = value.clone();
+    Ok(value)
+}
+
+// (swap! atom f args...) sets the atom to (f value args...) and returns the new value.
+// The atom is not borrowed while f runs, so f can read or change it.
+fn swap(ctx: &mut Context, mut args: Vec<RLType>) -> RLResult {
+    let rest = args.split_off(2);
+    let cell = cell(&args[0])?;
+
+    let mut call_args = vec![cell.borrow().clone()];
+    call_args.extend(rest);
+    let value = ctx.call(&args[1], call_args)?;
+    *cell.borrow_mut() = value.clone();
+    Ok(value)
+}
+
+#[test]
+fn atoms_hold_state_shared_by_their_holders() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+    let number = RLType::Number;
+
+    assert!(interp.eval_str("(def! counter (atom 0))").is_ok());
+    assert!(interp.eval_str("(def! same counter)").is_ok());
+    check(&mut interp, "(deref counter)", number(0.0));
+    check(&mut interp, "(swap! counter + 5)", number(5.0));
+    check(&mut interp, "(swap! same (lambda (n) (* n 2)))", number(10.0));
+    check(&mut interp, "@counter", number(10.0));
+    check(&mut interp, "(reset! counter (list 1))", RLType::List(vec![number(1.0)]));
+    check(&mut interp, "(pr-str counter)", RLType::Str("#<atom (1)>".to_string()));
+    check(&mut interp, "(swap! counter (lambda (l) (reset! counter 0)))", number(0.0));
+    check(&mut interp, "(atom? counter)", RLType::True);
+    check(&mut interp, "(atom? 0)", RLType::False);
+    check(&mut interp, "(= counter same)", RLType::True);
+    check(&mut interp, "(= (atom 1) (atom 1))", RLType::False);
+    check(&mut interp, "(type-of (box 1))", RLType::Symbol("atom".to_string()));
+
+    assert!(interp.eval_str("(def! b (box \"a\"))").is_ok());
+    check(&mut interp, "(set-box! b \"b\")", RLType::Str("b".to_string()));
+    check(&mut interp, "(unbox b)", RLType::Str("b".to_string()));
+
+    assert!(interp.eval_str("(deref 1)").is_err());
+    assert!(interp.eval_str("(swap! counter 1)").is_err());
+    assert!(interp.eval_str("(reset! counter)").is_err());
+    assert!(interp.eval_str("@").is_err());
+
+    // an atom holding itself is printed once
+    assert!(interp.eval_str("(def! a (atom 1))").is_ok());
+    check(&mut interp, "(pr-str (reset! a a))", RLType::Str("#<atom #<atom ...>>".to_string()));
+    assert!(interp.eval_str("(reset! a (list 1 a a))").is_ok());
+    let printed = "#<atom (1 #<atom ...> #<atom ...>)>";
+    check(&mut interp, "(pr-str a)", RLType::Str(printed.to_string()));
+    assert_eq!(interp.eval_str("a").map(|a| a.to_string()).ok(), Some(printed.to_string()));
+
+    assert!(interp.eval_str("(def! deref (lambda (x) \"wrong\"))").is_ok());
+    check(&mut interp, "@counter", number(0.0));
+}
@@ -0,0 +1,48 @@
+// This module contains basic functions that are not tied to a specific type
+
+use lisp::env::Env;
+use lisp::printer::pr_str;
+use lisp::types::*;
+
+// Adds this module's functions to the provided environment
+pub fn add_module(env: &mut Env) {
+    env.borrow_mut().insert("throw".to_string(), RLType::Proc(throw));
+    env.borrow_mut().insert("pr-str".to_string(), RLType::Proc(print_readably));
+    env.borrow_mut().insert("str".to_string(), RLType::Proc(print_display));
+}
+
+// Raises an error carrying the argument, which host code can retrieve from ErrorKind::Thrown
+fn throw(mut args: Vec<RLType>) -> RLResult {
+    if args.len() == 1 {
+        Err(RLError::new(ErrorKind::Thrown(args.remove(0))))
+    } else {
+        Err(RLError::arity("throw", Arity::Exact(1), args.len()))
+    }
+}
+
+// The readable forms of the arguments, separated by spaces
+fn print_readably(args: Vec<RLType>) -> RLResult {
+    let printed: Vec<String> = args.iter().map(|arg| pr_str(arg, true)).collect();
+    Ok(RLType::Str(printed.join(" ")))
+}
+
+// The display forms of the arguments, concatenated
+fn print_display(args: Vec<RLType>) -> RLResult {
+    let printed: Vec<String> = args.iter().map(|arg| pr_str(arg, false)).collect();
+    Ok(RLType::Str(printed.concat()))
+}
+
+#[test]
+fn base_prints_values() {
+    use lisp::interpreter::Interpreter;
+
+    let mut interp = Interpreter::new();
+    match interp.eval_str("(pr-str \"a\\n\" (list 1 \"b\") nil)") {
+        Ok(RLType::Str(ref text)) => assert_eq!(text, "\"a\\n\" (1 \"b\") nil"),
+        _ => panic!("expected a string"),
+    }
+    match interp.eval_str("(str \"a\" 1 (list \"b\" #t))") {
+        Ok(RLType::Str(ref text)) => assert_eq!(text, "a1(b #t)"),
+        _ => panic!("expected a string"),
+    }
+}
@@ -0,0 +1,135 @@
+// This module contains functions on chars and conversions between chars, numbers and strings
+
+use lisp::convert::IntoLisp;
+use lisp::env::Env;
+use lisp::types::*;
+
+// Adds this module's functions to the provided environment
+pub fn add_module(env: &mut Env) {
+    let functions: &[(&str, Builtin)] = &[
+        ("char?", is_char),
+        ("char->integer", char_to_integer),
+        ("integer->char", integer_to_char),
+        ("char-alphabetic?", is_alphabetic),
+        ("char-numeric?", is_numeric),
+        ("char-whitespace?", is_whitespace),
+        ("char-upcase", upcase),
+        ("char-downcase", downcase),
+        ("string->list", string_to_list),
+    ];
+    for &(name, function) in functions {
+        env.borrow_mut().insert(name.to_string(), RLType::Proc(function));
+    }
+}
+
+// The only argument of a function, which must be a char
+fn single_char(name: &str, args: &[RLType]) -> Result<char, RLError> {
+    match *args {
+        [RLType::Char(c)] => Ok(c),
+        [ref other] => Err(RLError::type_mismatch("char", other)),
+        _ => Err(RLError::arity(name, Arity::Exact(1), args.len())),
+    }
+}
+
+fn is_char(args: Vec<RLType>) -> RLResult {
+    match *args {
+        [ref value] => Ok(matches!(*value, RLType::Char(_)).into_lisp()),
+        _ => Err(RLError::arity("char?", Arity::Exact(1), args.len())),
+    }
+}
+
+// The Unicode code point of a char
+fn char_to_integer(args: Vec<RLType>) -> RLResult {
+    Ok((single_char("char->integer", &args)? as u32).into_lisp())
+}
+
+// The char of a Unicode code point, surrogates are not chars
+fn integer_to_char(args: Vec<RLType>) -> RLResult {
+    match *args {
+        [RLType::Number(n)] if n.fract() == 0.0 && n >= 0.0 && n <= u32::MAX as f64 => {
+            match ::std::char::from_u32(n as u32) {
+                Some(c) => Ok(RLType::Char(c)),
+                None => Err(RLError::new(ErrorKind::InvalidValue(args[0].clone()))),
+            }
+        },
+        [ref other] => Err(RLError::type_mismatch("code point", other)),
+        _ => Err(RLError::arity("integer->char", Arity::Exact(1), args.len())),
+    }
+}
+
+fn is_alphabetic(args: Vec<RLType>) -> RLResult {
+    Ok(single_char("char-alphabetic?", &args)?.is_alphabetic().into_lisp())
+}
+
+fn is_numeric(args: Vec<RLType>) -> RLResult {
+    Ok(single_char("char-numeric?", &args)?.is_numeric().into_lisp())
+}
+
+fn is_whitespace(args: Vec<RLType>) -> RLResult {
+    Ok(single_char("char-whitespace?", &args)?.is_whitespace().into_lisp())
+}
+
+// Chars whose upper or lower case is several chars, such as ß, are left unchanged
+fn upcase(args: Vec<RLType>) -> RLResult {
+    let c = single_char("char-upcase", &args)?;
+    Ok(RLType::Char(single_mapping(c, c.to_uppercase())))
+}
+
+fn downcase(args: Vec<RLType>) -> RLResult {
+    let c = single_char("char-downcase", &args)?;
+    Ok(RLType::Char(single_mapping(c, c.to_lowercase())))
+}
+
+fn single_mapping<I: Iterator<Item = char>>(c: char, mut mapped: I) -> char {
+    match (mapped.next(), mapped.next()) {
+        (Some(single), None) => single,
+        _ => c,
+    }
+}
+
+// The list of the chars of a string
+fn string_to_list(args: Vec<RLType>) -> RLResult {
+    match *args {
+        [RLType::Str(ref text)] => Ok(RLType::List(text.chars().map(RLType::Char).collect())),
+        [ref other] => Err(RLError::type_mismatch("string", other)),
+        _ => Err(RLError::arity("string->list", Arity::Exact(1), args.len())),
+    }
+}
+
+#[test]
+fn chars_convert_and_classify() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+
+    check(&mut interp, "#\\a", RLType::Char('a'));
+    check(&mut interp, "(char? #\\space)", RLType::True);
+    check(&mut interp, "(char? \"a\")", RLType::False);
+    check(&mut interp, "(char->integer #\\x41)", RLType::Number(65.0));
+    check(&mut interp, "(integer->char 955)", RLType::Char('λ'));
+    check(&mut interp, "(char-alphabetic? #\\é)", RLType::True);
+    check(&mut interp, "(char-alphabetic? #\\1)", RLType::False);
+    check(&mut interp, "(char-numeric? #\\7)", RLType::True);
+    check(&mut interp, "(char-whitespace? #\\newline)", RLType::True);
+    check(&mut interp, "(char-upcase #\\a)", RLType::Char('A'));
+    check(&mut interp, "(char-upcase #\\ß)", RLType::Char('ß'));
+    check(&mut interp, "(char-downcase #\\À)", RLType::Char('à'));
+    check(
+        &mut interp,
+        "(string->list \"a(→\")",
+        RLType::List(vec![RLType::Char('a'), RLType::Char('('), RLType::Char('→')]),
+    );
+    check(&mut interp, "(list->string (string->list \"a b\"))", RLType::Str("a b".to_string()));
+    check(
+        &mut interp,
+        "(list #\\( #\\) #\\;)",
+        RLType::List(vec![RLType::Char('('), RLType::Char(')'), RLType::Char(';')]),
+    );
+
+    assert!(interp.eval_str("(integer->char 55296)").is_err());
+    assert!(interp.eval_str("(integer->char -1)").is_err());
+    assert!(interp.eval_str("(char-upcase \"a\")").is_err());
+    assert!(interp.eval_str("#\\bogus").is_err());
+    assert!(interp.eval_str("#\\xzz").is_err());
+}
@@ -1,4 +1,8 @@
-// This module contains comparison functions for numeric values
+// This module contains comparison functions: ordering of numbers, chars and strings, equality of
+// any values
+
+use std::cmp::Ordering;
+use std::rc::Rc;
 
 use lisp::env::Env;
 use lisp::types::*;
@@ -10,82 +14,150 @@ pub fn add_module(env: &mut Env) {
     env.borrow_mut().insert("<".to_string(), RLType::Proc(lt));
     env.borrow_mut().insert("<=".to_string(), RLType::Proc(lte));
     env.borrow_mut().insert("=".to_string(), RLType::Proc(eq));
+    env.borrow_mut().insert("equal?".to_string(), RLType::Proc(equal));
+    env.borrow_mut().insert("eq?".to_string(), RLType::Proc(identical));
 }
 
 fn gt(args: Vec<RLType>) -> RLResult {
-    if args.len() == 2 {
-        match check_two_numbers(args) {
-            Ok((left, right)) => {
-                if left > right {Ok(RLType::True)} else {Ok(RLType::False)}
-            },
-            Err(e) => Err(e),
-        }
-    } else {
-        error(format!("Invalid number of arguments"))
-    }
+    compare(">", args, |order| order == Ordering::Greater)
 }
 
 fn gte(args: Vec<RLType>) -> RLResult {
-    if args.len() == 2 {
-        match check_two_numbers(args) {
-            Ok((left, right)) => {
-                if left >= right {Ok(RLType::True)} else {Ok(RLType::False)}
-            },
-            Err(e) => Err(e),
-        }
-    } else {
-        error(format!("Invalid number of arguments"))
-    }
+    compare(">=", args, |order| order != Ordering::Less)
 }
 
 fn lt(args: Vec<RLType>) -> RLResult {
-    if args.len() == 2 {
-        match check_two_numbers(args) {
-            Ok((left, right)) => {
-                if left < right {Ok(RLType::True)} else {Ok(RLType::False)}
-            },
-            Err(e) => Err(e),
-        }
-    } else {
-        error(format!("Invalid number of arguments"))
-    }
+    compare("<", args, |order| order == Ordering::Less)
 }
 
 fn lte(args: Vec<RLType>) -> RLResult {
-    if args.len() == 2 {
-        match check_two_numbers(args) {
-            Ok((left, right)) => {
-                if left <= right {Ok(RLType::True)} else {Ok(RLType::False)}
-            },
-            Err(e) => Err(e),
-        }
-    } else {
-        error(format!("Invalid number of arguments"))
-    }
+    compare("<=", args, |order| order != Ordering::Greater)
 }
 
+// True if all the arguments are structurally equal
 fn eq(args: Vec<RLType>) -> RLResult {
+    Arity::AtLeast(1).check("=", args.len())?;
+
+    Ok(boolean(args.windows(2).all(|pair| pair[0] == pair[1])))
+}
+
+fn equal(args: Vec<RLType>) -> RLResult {
     if args.len() == 2 {
-        match check_two_numbers(args) {
-            Ok((left, right)) => {
-                if left == right {Ok(RLType::True)} else {Ok(RLType::False)}
-            },
-            Err(e) => Err(e),
-        }
+        Ok(boolean(args[0] == args[1]))
     } else {
-        error(format!("Invalid number of arguments"))
+        Err(RLError::arity("equal?", Arity::Exact(2), args.len()))
     }
 }
 
-fn check_two_numbers(args: Vec<RLType>) -> Result<(f32, f32), RLError> {
-    let mut numbers = Vec::new();
+// True if the arguments are the same value. Lambdas, vectors, maps, sets and atoms are the
+// same when they share their data, that is when one is a copy of the other. Lists are copied
+// when they are evaluated, so no two lists are the same except empty ones. Other values are
+// the same when they are equal, builtins when they are the same function.
+fn identical(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(2).check("eq?", args.len())?;
 
-    for arg in args {
-        match arg {
-            RLType::Number(n) => numbers.push(n),
-            _ => return Err(RLError::Message(format!("Not a number: {}", arg))),
-        }
+    let same = match (&args[0], &args[1]) {
+        (RLType::List(a), RLType::List(b)) => a.is_empty() && b.is_empty(),
+        (RLType::Lambda(a), RLType::Lambda(b)) => Rc::ptr_eq(&a.ast, &b.ast),
+        (RLType::Vector(a), RLType::Vector(b)) => a.ptr_eq(b),
+        (RLType::Map(a), RLType::Map(b)) => a.ptr_eq(b),
+        (RLType::Set(a), RLType::Set(b)) => a.ptr_eq(b),
+        (RLType::Atom(a), RLType::Atom(b)) => Rc::ptr_eq(a, b),
+        (a, b) => a == b,
+    };
+    Ok(boolean(same))
+}
+
+// Checks that each argument is in the accepted order relative to the next one.
+// The arguments must be all numbers, all chars or all strings, comparisons with NaN are false.
+fn compare(name: &str, args: Vec<RLType>, accept: fn(Ordering) -> bool) -> RLResult {
+    Arity::AtLeast(2).check(name, args.len())?;
+
+    let expected = match args[0] {
+        RLType::Number(_) => "number",
+        RLType::Char(_) => "char",
+        RLType::Str(_) => "string",
+        _ => return Err(RLError::type_mismatch("number, char or string", &args[0])),
+    };
+
+    let mut result = true;
+    for pair in args.windows(2) {
+        let order = match (&pair[0], &pair[1]) {
+            (RLType::Number(a), RLType::Number(b)) => a.partial_cmp(b),
+            (RLType::Char(a), RLType::Char(b)) => Some(a.cmp(b)),
+            (RLType::Str(a), RLType::Str(b)) => Some(a.cmp(b)),
+            (_, other) => return Err(RLError::type_mismatch(expected, other)),
+        };
+        result = result && order.is_some_and(accept);
     }
+    Ok(boolean(result))
+}
+
+fn boolean(value: bool) -> RLType {
+    if value { RLType::True } else { RLType::False }
+}
+
+#[test]
+fn comparison_chains_numbers_and_strings() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+
+    check(&mut interp, "(< 1 2 3)", RLType::True);
+    check(&mut interp, "(< 1 3 2)", RLType::False);
+    check(&mut interp, "(<= 1 1 2)", RLType::True);
+    check(&mut interp, "(> 3 2 2)", RLType::False);
+    check(&mut interp, "(>= 3 2 2)", RLType::True);
+    check(&mut interp, "(< \"apple\" \"banana\" \"cherry\")", RLType::True);
+    check(&mut interp, "(> \"b\" \"a\")", RLType::True);
+    check(&mut interp, "(< #\\a #\\b #\\é)", RLType::True);
+
+    assert!(interp.eval_str("(< 1 \"a\")").is_err());
+    assert!(interp.eval_str("(< \"a\" 1)").is_err());
+    assert!(interp.eval_str("(< nil 1)").is_err());
+    // every argument is checked, even once the result is known
+    assert!(interp.eval_str("(< 2 1 \"a\")").is_err());
+}
+
+#[test]
+fn comparison_tests_equality_of_any_values() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+
+    check(&mut interp, "(= 1 1 1)", RLType::True);
+    check(&mut interp, "(= 1 1 2)", RLType::False);
+    check(&mut interp, "(= 1)", RLType::True);
+    check(&mut interp, "(= nil nil)", RLType::True);
+    check(&mut interp, "(= #t #f)", RLType::False);
+    check(&mut interp, "(= 1 \"1\")", RLType::False);
+    let nested = "(equal? (list 1 (list \"a\" nil)) (list 1 (list \"a\" nil)))";
+    check(&mut interp, nested, RLType::True);
+    check(&mut interp, "(equal? (list 1 2) (list 1 2 3))", RLType::False);
+    check(&mut interp, "(equal? + +)", RLType::True);
+    check(&mut interp, "(equal? + -)", RLType::False);
+
+    check(&mut interp, "(eq? 1 1)", RLType::True);
+    check(&mut interp, "(eq? + +)", RLType::True);
+    check(&mut interp, "(eq? (list) (list))", RLType::True);
+    check(&mut interp, "(eq? (list 1) (list 1))", RLType::False);
 
-    Ok((numbers[0], numbers[1]))
+    assert!(interp.eval_str("(def! f (lambda (x) x))").is_ok());
+    assert!(interp.eval_str("(def! g f)").is_ok());
+    check(&mut interp, "(eq? f f)", RLType::True);
+    check(&mut interp, "(eq? f g)", RLType::True);
+    check(&mut interp, "(eq? f (lambda (x) x))", RLType::False);
+    assert!(interp.eval_str("(def! m {:a 1})").is_ok());
+    check(&mut interp, "(eq? m m)", RLType::True);
+    check(&mut interp, "(eq? {:a 1} {:a 1})", RLType::False);
+    check(&mut interp, "(eq? m (assoc m :a 1))", RLType::False);
+    check(&mut interp, "(= m (assoc m :a 1))", RLType::True);
+    assert!(interp.eval_str("(def! s #{1})").is_ok());
+    check(&mut interp, "(eq? s s)", RLType::True);
+    check(&mut interp, "(eq? #{1} #{1})", RLType::False);
+    check(&mut interp, "(eq? (atom 1) (atom 1))", RLType::False);
+    assert!(interp.eval_str("(=)").is_err());
+    assert!(interp.eval_str("(equal? 1)").is_err());
 }
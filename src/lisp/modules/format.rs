@@ -0,0 +1,161 @@
+// This module contains format, which builds a string from a template and arguments.
+// Directives start with ~ and take an optional width and precision, as in ~8,2f:
+//   ~a  the display form of an argument, padded on the right to the width
+//   ~s  the readable form of an argument, padded on the right to the width
+//   ~d  an integer, padded on the left to the width
+//   ~f  a number with precision digits after the point, padded on the left to the width
+//   ~%  a newline
+//   ~~  a tilde
+
+use lisp::env::Env;
+use lisp::printer::pr_str;
+use lisp::types::*;
+
+// Adds this module's functions to the provided environment
+pub fn add_module(env: &mut Env) {
+    env.borrow_mut().insert("format".to_string(), RLType::Proc(format));
+}
+
+// Also called by the interpolated strings read by the reader
+pub fn format(args: Vec<RLType>) -> RLResult {
+    let template = match args.first() {
+        Some(RLType::Str(template)) => template,
+        Some(other) => return Err(RLError::type_mismatch("string", other)),
+        None => return Err(RLError::arity("format", Arity::AtLeast(1), 0)),
+    };
+
+    let mut values = args[1..].iter();
+    let mut result = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '~' {
+            result.push(c);
+            continue;
+        }
+
+        let width = read_digits(&mut chars);
+        let precision = if chars.peek() == Some(&',') {
+            chars.next();
+            read_digits(&mut chars)
+        } else {
+            None
+        };
+
+        let directive = match chars.next() {
+            Some(directive) => directive.to_ascii_lowercase(),
+            None => return error("format: the template ends with ~".to_string()),
+        };
+        match directive {
+            '%' => result.push('\n'),
+            '~' => result.push('~'),
+            'a' | 's' | 'd' | 'f' => {
+                let value = match values.next() {
+                    Some(value) => value,
+                    None => return error(format!("format: no argument for ~{}", directive)),
+                };
+                let text = format_value(directive, value, precision)?;
+                pad(&mut result, &text, width.unwrap_or(0), directive == 'a' || directive == 's');
+            },
+            other => return error(format!("format: unknown directive ~{}", other)),
+        }
+    }
+
+    if values.next().is_some() {
+        return error("format: more arguments than directives".to_string());
+    }
+    Ok(RLType::Str(result))
+}
+
+fn read_digits<I: Iterator<Item = char>>(chars: &mut ::std::iter::Peekable<I>) -> Option<usize> {
+    let mut number = None;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        number = Some(number.unwrap_or(0) * 10 + digit as usize);
+        chars.next();
+    }
+    number
+}
+
+fn format_value(directive: char, value: &RLType,
+                precision: Option<usize>) -> Result<String, RLError> {
+    match (directive, value) {
+        ('a', _) => Ok(pr_str(value, false)),
+        ('s', _) => Ok(pr_str(value, true)),
+        ('d', &RLType::Number(n)) if n.fract() == 0.0 => Ok(pr_str(value, true)),
+        ('d', _) => Err(RLError::type_mismatch("integer", value)),
+        ('f', &RLType::Number(n)) if n.is_finite() => match precision {
+            Some(precision) => Ok(format!("{:.*}", precision, n)),
+            None => Ok(pr_str(value, true)),
+        },
+        ('f', &RLType::Number(_)) => Ok(pr_str(value, true)),
+        _ => Err(RLError::type_mismatch("number", value)),
+    }
+}
+
+// Appends text padded with spaces to width chars, on the right or on the left
+fn pad(result: &mut String, text: &str, width: usize, on_right: bool) {
+    let padding = " ".repeat(width.saturating_sub(text.chars().count()));
+    if on_right {
+        result.push_str(text);
+        result.push_str(&padding);
+    } else {
+        result.push_str(&padding);
+        result.push_str(text);
+    }
+}
+
+#[test]
+fn format_applies_directives() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+    let string = |text: &str| RLType::Str(text.to_string());
+
+    check(&mut interp, "(format \"~a has ~d items~%\" \"cart\" 3)", string("cart has 3 items\n"));
+    check(&mut interp, "(format \"~s and ~a\" \"x\" \"x\")", string("\"x\" and x"));
+    check(&mut interp, "(format \"~a\" (list 1 \"b\"))", string("(1 b)"));
+    let padded = "(format \"[~5a][~5d][~3a]\" \"ab\" 42 \"long\")";
+    check(&mut interp, padded, string("[ab   ][   42][long]"));
+    check(&mut interp, "(format \"~,2f ~8,3f ~f\" pi 2.5 0.5)", string("3.14    2.500 0.5"));
+    check(&mut interp, "(format \"~,1f ~,1f\" -inf.0 -0.04)", string("-inf.0 -0.0"));
+    check(&mut interp, "(format \"100~~ ~A\" #t)", string("100~ #t"));
+    check(&mut interp, "(format \"no directives\")", string("no directives"));
+
+    assert!(interp.eval_str("(format \"~a ~a\" 1)").is_err());
+    assert!(interp.eval_str("(format \"~a\" 1 2)").is_err());
+    assert!(interp.eval_str("(format \"~q\" 1)").is_err());
+    assert!(interp.eval_str("(format \"~d\" 1.5)").is_err());
+    assert!(interp.eval_str("(format \"~f\" \"1\")").is_err());
+    assert!(interp.eval_str("(format \"50~\")").is_err());
+    assert!(interp.eval_str("(format 1)").is_err());
+}
+
+#[test]
+fn format_interpolates_strings() {
+    use lisp::interpreter::Interpreter;
+
+    let mut interp = Interpreter::new();
+    assert!(interp.eval_str("(def! name \"Ada\")").is_ok());
+    assert!(interp.eval_str("(def! items (list 1 2))").is_ok());
+
+    match interp.eval_str("#\"Hello ${name}, ${(+ 1 1)} items: ${items}~\"") {
+        Ok(RLType::Str(ref text)) => assert_eq!(text, "Hello Ada, 2 items: (1 2)~"),
+        other => panic!("got {:?}", other.map_err(|e| e.to_string())),
+    }
+
+    // the reader calls the builtin, whatever format is bound to
+    let mut shadowed = Interpreter::new();
+    assert!(shadowed.eval_str("(def! format (lambda (t) \"wrong\"))").is_ok());
+    assert_eq!(shadowed.eval_str("#\"${(+ 1 2)}\"").ok(), Some(RLType::Str("3".to_string())));
+
+    // an escaped ${ doesn't need a closing brace
+    match interp.eval_str("(str #\"a \\${\" \"b\") (def! after 1)") {
+        Ok(RLType::Nil) => (),
+        other => panic!("got {:?}", other.map_err(|e| e.to_string())),
+    }
+    match interp.eval_str("(str #\"a \\${\" \"b\" after)") {
+        Ok(RLType::Str(ref text)) => assert_eq!(text, "a ${b1"),
+        other => panic!("got {:?}", other.map_err(|e| e.to_string())),
+    }
+}
@@ -0,0 +1,236 @@
+// This module contains functions on hash maps. Maps are persistent values: assoc, dissoc,
+// merge and update return a new map sharing most of its structure with their argument, which
+// is left unchanged. Any value can be a key.
+
+use lisp::context::Context;
+use lisp::convert::IntoLisp;
+use lisp::env::Env;
+use lisp::modules::sets;
+use lisp::modules::vectors;
+use lisp::persistent::PMap;
+use lisp::types::*;
+
+// Adds this module's functions to the provided environment
+pub fn add_module(env: &mut Env) {
+    let functions: &[(&str, Builtin)] = &[
+        ("hash-map", hash_map),
+        ("map?", is_map),
+        ("get", get),
+        ("assoc", assoc),
+        ("dissoc", dissoc),
+        ("contains?", contains),
+        ("keys", keys),
+        ("vals", vals),
+        ("entries", entries),
+        ("merge", merge),
+    ];
+    for &(name, function) in functions {
+        env.borrow_mut().insert(name.to_string(), RLType::Proc(function));
+    }
+    env.borrow_mut().insert_native("update", Arity::AtLeast(3), update);
+    env.borrow_mut().insert_native("reduce-kv", Arity::Exact(3), reduce_kv);
+}
+
+// Takes the map that is the first argument
+fn map(args: &mut Vec<RLType>) -> Result<PMap<RLType, RLType>, RLError> {
+    match args.remove(0) {
+        RLType::Map(entries) => Ok(entries),
+        other => Err(RLError::type_mismatch("map", &other)),
+    }
+}
+
+// Adds key value pairs to entries, later values replace earlier ones
+fn insert_pairs(name: &str, mut entries: PMap<RLType, RLType>, args: Vec<RLType>) -> RLResult {
+    if !args.len().is_multiple_of(2) {
+        return error(format!("{}: expected a value for each key", name));
+    }
+
+    let mut args = args.into_iter();
+    while let (Some(key), Some(value)) = (args.next(), args.next()) {
+        entries.insert(key, value);
+    }
+    Ok(RLType::Map(entries))
+}
+
+// Also called by the map literals read by the reader
+pub fn hash_map(args: Vec<RLType>) -> RLResult {
+    insert_pairs("hash-map", PMap::new(), args)
+}
+
+fn is_map(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(1).check("map?", args.len())?;
+    Ok(matches!(args[0], RLType::Map(_)).into_lisp())
+}
+
+// The value of a key, or a default value which is nil when not given
+fn get(mut args: Vec<RLType>) -> RLResult {
+    Arity::Range(2, 3).check("get", args.len())?;
+    let entries = map(&mut args)?;
+    let default = args.get(1).cloned().unwrap_or(RLType::Nil);
+    Ok(entries.get(&args[0]).cloned().unwrap_or(default))
+}
+
+// Sets keys of a map, or indexes of a vector
+fn assoc(mut args: Vec<RLType>) -> RLResult {
+    Arity::AtLeast(3).check("assoc", args.len())?;
+    if let RLType::Vector(_) = args[0] {
+        return vectors::assoc(args);
+    }
+    let entries = map(&mut args)?;
+    insert_pairs("assoc", entries, args)
+}
+
+fn dissoc(mut args: Vec<RLType>) -> RLResult {
+    Arity::AtLeast(1).check("dissoc", args.len())?;
+    let mut entries = map(&mut args)?;
+    for key in &args {
+        entries.remove(key);
+    }
+    Ok(RLType::Map(entries))
+}
+
+// True if a map has a key, or a set a value
+fn contains(mut args: Vec<RLType>) -> RLResult {
+    Arity::Exact(2).check("contains?", args.len())?;
+    if let RLType::Set(_) = args[0] {
+        return sets::contains(args);
+    }
+    let entries = map(&mut args)?;
+    Ok(entries.contains_key(&args[0]).into_lisp())
+}
+
+// The keys of a map, in the same order as vals and entries
+fn keys(mut args: Vec<RLType>) -> RLResult {
+    Arity::Exact(1).check("keys", args.len())?;
+    Ok(RLType::List(map(&mut args)?.keys().cloned().collect()))
+}
+
+fn vals(mut args: Vec<RLType>) -> RLResult {
+    Arity::Exact(1).check("vals", args.len())?;
+    Ok(RLType::List(map(&mut args)?.values().cloned().collect()))
+}
+
+// The (key value) pairs of a map
+fn entries(mut args: Vec<RLType>) -> RLResult {
+    Arity::Exact(1).check("entries", args.len())?;
+    let entries = map(&mut args)?;
+    let pairs = entries.iter().map(|(key, value)| RLType::List(vec![key.clone(), value.clone()]));
+    Ok(RLType::List(pairs.collect()))
+}
+
+// The entries of all the maps, for keys in several maps the value of the last one
+fn merge(args: Vec<RLType>) -> RLResult {
+    let mut merged = PMap::new();
+    for arg in args {
+        match arg {
+            // the first map is kept whole, the entries of the others are added to it
+            RLType::Map(entries) if merged.is_empty() => merged = entries,
+            RLType::Map(entries) => {
+                merged.extend(entries.iter().map(|(k, v)| (k.clone(), v.clone())))
+            },
+            other => return Err(RLError::type_mismatch("map", &other)),
+        }
+    }
+    Ok(RLType::Map(merged))
+}
+
+// (update map key f args...) sets key to (f value args...), value is nil for a missing key
+fn update(ctx: &mut Context, mut args: Vec<RLType>) -> RLResult {
+    let mut entries = map(&mut args)?;
+    let key = args.remove(0);
+    let func = args.remove(0);
+
+    let value = entries.get(&key).cloned().unwrap_or(RLType::Nil);
+    args.insert(0, value);
+    let value = ctx.call(&func, args)?;
+    entries.insert(key, value);
+    Ok(RLType::Map(entries))
+}
+
+// (reduce-kv f init map) calls (f result key value) for every entry, starting with init
+fn reduce_kv(ctx: &mut Context, mut args: Vec<RLType>) -> RLResult {
+    let func = args.remove(0);
+    let mut result = args.remove(0);
+    for (key, value) in map(&mut args)?.iter() {
+        result = ctx.call(&func, vec![result, key.clone(), value.clone()])?;
+    }
+    Ok(result)
+}
+
+#[test]
+fn maps_literals_evaluate_keys_and_values() {
+    use lisp::interpreter::Interpreter;
+
+    let mut interp = Interpreter::new();
+    assert!(interp.eval_str("(def! k \"b\")").is_ok());
+
+    let mut expected = PMap::new();
+    expected.insert(RLType::Str("a".to_string()), RLType::Number(1.0));
+    expected.insert(RLType::Str("b".to_string()), RLType::Number(3.0));
+    assert_eq!(interp.eval_str("{\"a\" 1 k (+ 1 2)}").ok(), Some(RLType::Map(expected)));
+    assert_eq!(interp.eval_str("{}").ok(), Some(RLType::Map(PMap::new())));
+
+    let mut shadowed = Interpreter::new();
+    assert!(shadowed.eval_str("(def! hash-map (lambda (k v) \"wrong\"))").is_ok());
+    assert_eq!(shadowed.eval_str("(map? {1 2})").ok(), Some(RLType::True));
+
+    assert!(interp.eval_str("{\"a\"}").is_err());
+    assert!(interp.eval_str("{\"a\" 1)").is_err());
+}
+
+#[test]
+fn maps_functions_return_new_maps() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+    let number = RLType::Number;
+
+    assert!(interp.eval_str("(def! m {\"a\" 1 (list 1 2) \"list\" 2.0 #t})").is_ok());
+    check(&mut interp, "(get m \"a\")", number(1.0));
+    check(&mut interp, "(get m (list 1 2))", RLType::Str("list".to_string()));
+    check(&mut interp, "(get m 2)", RLType::True);
+    check(&mut interp, "(get m \"z\")", RLType::Nil);
+    check(&mut interp, "(get m \"z\" 0)", number(0.0));
+    check(&mut interp, "(contains? m \"a\")", RLType::True);
+    check(&mut interp, "(contains? (dissoc m \"a\" 2) \"a\")", RLType::False);
+    check(&mut interp, "(get (assoc m \"a\" 10 \"b\" 20) \"b\")", number(20.0));
+    check(&mut interp, "(get m \"b\")", RLType::Nil);
+    check(&mut interp, "(get (merge m {\"a\" 5} {\"c\" 6}) \"a\")", number(5.0));
+    check(&mut interp, "(get (update m \"a\" + 10) \"a\")", number(11.0));
+    check(&mut interp, "(get (update m \"n\" (lambda (v) (nil? v))) \"n\")", RLType::True);
+    check(&mut interp, "(reduce-kv (lambda (sum k v) (+ sum v)) 0 {\"a\" 1 \"b\" 2})", number(3.0));
+    check(&mut interp, "(= (keys {\"a\" 1}) (list \"a\"))", RLType::True);
+    check(&mut interp, "(= (vals {\"a\" 1}) (list 1))", RLType::True);
+    check(&mut interp, "(entries {\"a\" 1})", RLType::List(vec![
+        RLType::List(vec![RLType::Str("a".to_string()), number(1.0)]),
+    ]));
+    check(&mut interp, "(= {1 {\"x\" 2} 3 4} {3 4 1 {\"x\" 2}})", RLType::True);
+    check(&mut interp, "(get {{1 2 3 4} \"nested\"} {3 4 1 2})", RLType::Str("nested".to_string()));
+    check(&mut interp, "(get {0 \"zero\"} -0)", RLType::Str("zero".to_string()));
+    check(&mut interp, "(map? m)", RLType::True);
+    check(&mut interp, "(type-of m)", RLType::Symbol("map".to_string()));
+
+    assert!(interp.eval_str("(get (list) 1)").is_err());
+    assert!(interp.eval_str("(assoc m 1)").is_err());
+    assert!(interp.eval_str("(assoc m 1 2 3)").is_err());
+    assert!(interp.eval_str("(merge m 1)").is_err());
+}
+
+#[test]
+fn maps_share_structure_with_their_originals() {
+    use lisp::interpreter::Interpreter;
+
+    let mut interp = Interpreter::new();
+    let big: PMap<RLType, RLType> = (0..100_000)
+        .map(|i| (RLType::Number(i as f64), RLType::True))
+        .collect();
+    interp.env().borrow_mut().insert("big".to_string(), RLType::Map(big));
+    assert!(interp.eval_str("(def! changed (dissoc (assoc big \"new\" 1) 7))").is_ok());
+
+    assert_eq!(interp.eval_str("(get big \"new\")").ok(), Some(RLType::Nil));
+    assert_eq!(interp.eval_str("(get big 7)").ok(), Some(RLType::True));
+    assert_eq!(interp.eval_str("(get changed \"new\")").ok(), Some(RLType::Number(1.0)));
+    assert_eq!(interp.eval_str("(contains? changed 7)").ok(), Some(RLType::False));
+    assert_eq!(interp.eval_str("(get (merge big changed) 7)").ok(), Some(RLType::True));
+}
@@ -1,84 +1,390 @@
-// This module contains comparison functions for numeric values
+// This module contains numeric functions and constants
+
+use std;
 
 use lisp::env::Env;
 use lisp::types::*;
 
-
 // Adds this module's functions to the provided environment
 pub fn add_module(env: &mut Env) {
-    env.borrow_mut().insert("+".to_string(), RLType::Proc(add));
-    env.borrow_mut().insert("-".to_string(), RLType::Proc(sub));
-    env.borrow_mut().insert("*".to_string(), RLType::Proc(mul));
-    env.borrow_mut().insert("/".to_string(), RLType::Proc(div));
+    let constants = [("pi", std::f64::consts::PI), ("e", std::f64::consts::E)];
+    for &(name, value) in &constants {
+        env.borrow_mut().insert(name.to_string(), RLType::Number(value));
+    }
+
+    let functions: &[(&str, Builtin)] = &[
+        ("+", add),
+        ("-", sub),
+        ("*", mul),
+        ("/", div),
+        ("mod", modulo),
+        ("rem", rem),
+        ("quot", quot),
+        ("expt", expt),
+        ("sqrt", sqrt),
+        ("cbrt", cbrt),
+        ("exp", exp),
+        ("log", log),
+        ("sin", sin),
+        ("cos", cos),
+        ("tan", tan),
+        ("asin", asin),
+        ("acos", acos),
+        ("atan", atan),
+        ("sinh", sinh),
+        ("cosh", cosh),
+        ("tanh", tanh),
+        ("floor", floor),
+        ("ceil", ceil),
+        ("round", round),
+        ("truncate", truncate),
+        ("abs", abs),
+        ("min", min),
+        ("max", max),
+        ("gcd", gcd),
+        ("lcm", lcm),
+        ("nan?", is_nan),
+        ("finite?", is_finite),
+        ("infinite?", is_infinite),
+    ];
+    for &(name, function) in functions {
+        env.borrow_mut().insert(name.to_string(), RLType::Proc(function));
+    }
 }
 
-fn add(args: Vec<RLType>) -> RLResult {
-    if args.len() >= 2 {
-        let mut sum: f32 = 0.0;
-        for arg in args {
-            sum += match arg {
-                RLType::Number(n) => n,
-                _ => return error(format!("Not a number: {}", arg)),
-            }
+// The arguments as numbers, after checking their count
+fn numbers(name: &str, arity: Arity, args: &[RLType]) -> Result<Vec<f64>, RLError> {
+    arity.check(name, args.len())?;
+
+    args.iter()
+        .map(|arg| match *arg {
+            RLType::Number(n) => Ok(n),
+            _ => Err(RLError::type_mismatch("number", arg)),
+        })
+        .collect()
+}
+
+// The arguments as integers, i.e. numbers without a fractional part
+fn integers(name: &str, arity: Arity, args: &[RLType]) -> Result<Vec<f64>, RLError> {
+    let values = numbers(name, arity, args)?;
+    for (value, arg) in values.iter().zip(args) {
+        if value.fract() != 0.0 {
+            return Err(RLError::type_mismatch("integer", arg));
         }
-        Ok(RLType::Number(sum))
+    }
+    Ok(values)
+}
+
+fn non_zero(divisor: f64) -> Result<f64, RLError> {
+    if divisor == 0.0 {
+        Err(RLError::new(ErrorKind::DivisionByZero))
     } else {
-        error(format!("Invalid number of arguments"))
+        Ok(divisor)
     }
 }
 
+// Applies a function of one number
+fn unary(name: &str, args: Vec<RLType>, function: fn(f64) -> f64) -> RLResult {
+    let values = numbers(name, Arity::Exact(1), &args)?;
+    Ok(RLType::Number(function(values[0])))
+}
+
+// Tests a property of a number
+fn predicate(name: &str, args: Vec<RLType>, test: fn(f64) -> bool) -> RLResult {
+    let values = numbers(name, Arity::Exact(1), &args)?;
+    Ok(if test(values[0]) { RLType::True } else { RLType::False })
+}
+
+// Applies a function of two numbers, the second being a non-zero divisor
+fn division(name: &str, args: Vec<RLType>, function: fn(f64, f64) -> f64) -> RLResult {
+    let values = numbers(name, Arity::Exact(2), &args)?;
+    let divisor = non_zero(values[1])?;
+    Ok(RLType::Number(function(values[0], divisor)))
+}
+
+fn add(args: Vec<RLType>) -> RLResult {
+    let values = numbers("+", Arity::AtLeast(0), &args)?;
+    Ok(RLType::Number(values.iter().sum()))
+}
+
+// Subtracts the other arguments from the first one, or negates a single argument
 fn sub(args: Vec<RLType>) -> RLResult {
-    if args.len() >= 2 {
-        let mut result: f32 = match args[0] {
-            RLType::Number(n) => n,
-            _ => return error(format!("Not a number: {}", args[0])),
-        };
-
-        for i in 1..args.len() {
-            result -= match args[i] {
-                RLType::Number(n) => n,
-                _ => return error(format!("Not a number: {}", args[i])),
-            }
-        }
+    let values = numbers("-", Arity::AtLeast(1), &args)?;
+    if values.len() == 1 {
+        return Ok(RLType::Number(-values[0]));
+    }
+
+    Ok(RLType::Number(values[1..].iter().fold(values[0], |result, n| result - n)))
+}
 
-        Ok(RLType::Number(result))
+fn mul(args: Vec<RLType>) -> RLResult {
+    let values = numbers("*", Arity::AtLeast(0), &args)?;
+    Ok(RLType::Number(values.iter().product()))
+}
+
+// Divides the first argument by the others, or returns the inverse of a single argument
+fn div(args: Vec<RLType>) -> RLResult {
+    let values = numbers("/", Arity::AtLeast(1), &args)?;
+    let (mut result, divisors) = if values.len() == 1 {
+        (1.0, 0..1)
     } else {
-        error(format!("Invalid number of arguments"))
+        (values[0], 1..values.len())
+    };
+
+    for i in divisors {
+        result /= non_zero(values[i])?;
     }
+    Ok(RLType::Number(result))
 }
 
+// The remainder of a floored division, with the sign of the divisor
+fn modulo(args: Vec<RLType>) -> RLResult {
+    division("mod", args, |a, b| a - b * (a / b).floor())
+}
 
-fn mul(args: Vec<RLType>) -> RLResult {
-    if args.len() >= 2 {
-        let mut total: f32 = 1.0;
-        for arg in args {
-            total *= match arg {
-                RLType::Number(n) => n,
-                _ => return error(format!("Not a number: {}", arg)),
-            }
-        }
-        Ok(RLType::Number(total))
-    } else {
-        error(format!("Invalid number of arguments"))
+// The remainder of a truncated division, with the sign of the dividend
+fn rem(args: Vec<RLType>) -> RLResult {
+    division("rem", args, |a, b| a % b)
+}
+
+// The quotient of a division, truncated towards zero
+fn quot(args: Vec<RLType>) -> RLResult {
+    division("quot", args, |a, b| (a / b).trunc())
+}
+
+fn expt(args: Vec<RLType>) -> RLResult {
+    let values = numbers("expt", Arity::Exact(2), &args)?;
+    Ok(RLType::Number(values[0].powf(values[1])))
+}
+
+fn sqrt(args: Vec<RLType>) -> RLResult {
+    unary("sqrt", args, f64::sqrt)
+}
+
+fn cbrt(args: Vec<RLType>) -> RLResult {
+    unary("cbrt", args, f64::cbrt)
+}
+
+fn exp(args: Vec<RLType>) -> RLResult {
+    unary("exp", args, f64::exp)
+}
+
+// The natural logarithm, or the logarithm in the base given as second argument
+fn log(args: Vec<RLType>) -> RLResult {
+    let values = numbers("log", Arity::Range(1, 2), &args)?;
+    match values.get(1) {
+        Some(&base) => Ok(RLType::Number(values[0].log(base))),
+        None => Ok(RLType::Number(values[0].ln())),
     }
 }
 
-fn div(args: Vec<RLType>) -> RLResult {
-    if args.len() >= 2 {
-        let mut result: f32 = match args[0] {
-            RLType::Number(n) => n,
-            _ => return error(format!("Not a number: {}", args[0])),
-        };
-
-        for i in 1..args.len() {
-            result /= match args[i] {
-                RLType::Number(n) if n != 0.0 => n,
-                _ => return error(format!("Not a number: {}", args[i])),
-            }
-        }
+fn sin(args: Vec<RLType>) -> RLResult {
+    unary("sin", args, f64::sin)
+}
 
-        Ok(RLType::Number(result))
-    } else {
-        error(format!("Invalid number of arguments"))
+fn cos(args: Vec<RLType>) -> RLResult {
+    unary("cos", args, f64::cos)
+}
+
+fn tan(args: Vec<RLType>) -> RLResult {
+    unary("tan", args, f64::tan)
+}
+
+fn asin(args: Vec<RLType>) -> RLResult {
+    unary("asin", args, f64::asin)
+}
+
+fn acos(args: Vec<RLType>) -> RLResult {
+    unary("acos", args, f64::acos)
+}
+
+// The arc tangent of y, or with two arguments of y/x using their signs to find the quadrant
+fn atan(args: Vec<RLType>) -> RLResult {
+    let values = numbers("atan", Arity::Range(1, 2), &args)?;
+    match values.get(1) {
+        Some(&x) => Ok(RLType::Number(values[0].atan2(x))),
+        None => Ok(RLType::Number(values[0].atan())),
+    }
+}
+
+fn sinh(args: Vec<RLType>) -> RLResult {
+    unary("sinh", args, f64::sinh)
+}
+
+fn cosh(args: Vec<RLType>) -> RLResult {
+    unary("cosh", args, f64::cosh)
+}
+
+fn tanh(args: Vec<RLType>) -> RLResult {
+    unary("tanh", args, f64::tanh)
+}
+
+fn floor(args: Vec<RLType>) -> RLResult {
+    unary("floor", args, f64::floor)
+}
+
+fn ceil(args: Vec<RLType>) -> RLResult {
+    unary("ceil", args, f64::ceil)
+}
+
+// Rounds to the nearest integer, halfway cases to the even one as in Scheme
+fn round(args: Vec<RLType>) -> RLResult {
+    unary("round", args, f64::round_ties_even)
+}
+
+fn truncate(args: Vec<RLType>) -> RLResult {
+    unary("truncate", args, f64::trunc)
+}
+
+fn abs(args: Vec<RLType>) -> RLResult {
+    unary("abs", args, f64::abs)
+}
+
+// The smallest argument, NaN if any argument is NaN
+fn min(args: Vec<RLType>) -> RLResult {
+    let values = numbers("min", Arity::AtLeast(1), &args)?;
+    if values.iter().any(|n| n.is_nan()) {
+        return Ok(RLType::Number(f64::NAN));
+    }
+    Ok(RLType::Number(values.iter().fold(f64::INFINITY, |a, &b| a.min(b))))
+}
+
+// The largest argument, NaN if any argument is NaN
+fn max(args: Vec<RLType>) -> RLResult {
+    let values = numbers("max", Arity::AtLeast(1), &args)?;
+    if values.iter().any(|n| n.is_nan()) {
+        return Ok(RLType::Number(f64::NAN));
+    }
+    Ok(RLType::Number(values.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b))))
+}
+
+fn is_nan(args: Vec<RLType>) -> RLResult {
+    predicate("nan?", args, f64::is_nan)
+}
+
+fn is_finite(args: Vec<RLType>) -> RLResult {
+    predicate("finite?", args, f64::is_finite)
+}
+
+fn is_infinite(args: Vec<RLType>) -> RLResult {
+    predicate("infinite?", args, f64::is_infinite)
+}
+
+fn gcd_of(a: f64, b: f64) -> f64 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0.0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+// The greatest common divisor of integers, 0 without arguments
+fn gcd(args: Vec<RLType>) -> RLResult {
+    let values = integers("gcd", Arity::AtLeast(0), &args)?;
+    Ok(RLType::Number(values.iter().fold(0.0, |a, &b| gcd_of(a, b))))
+}
+
+// The least common multiple of integers, 1 without arguments
+fn lcm(args: Vec<RLType>) -> RLResult {
+    let values = integers("lcm", Arity::AtLeast(0), &args)?;
+    let result = values.iter().fold(1.0, |a, &b| {
+        if a == 0.0 || b == 0.0 { 0.0 } else { (a * b).abs() / gcd_of(a, b) }
+    });
+    Ok(RLType::Number(result))
+}
+
+#[cfg(test)]
+fn eval_number(code: &str) -> f64 {
+    use lisp::interpreter::Interpreter;
+
+    match Interpreter::new().eval_str(code) {
+        Ok(RLType::Number(n)) => n,
+        other => panic!("{} returned {:?}", code, other.map_err(|e| e.to_string())),
+    }
+}
+
+#[test]
+fn math_follows_scheme_arithmetic() {
+    use lisp::interpreter::Interpreter;
+
+    assert_eq!(eval_number("(+)"), 0.0);
+    assert_eq!(eval_number("(+ 1 2 3)"), 6.0);
+    assert_eq!(eval_number("(- 5)"), -5.0);
+    assert_eq!(eval_number("(- 10 1 2)"), 7.0);
+    assert_eq!(eval_number("(*)"), 1.0);
+    assert_eq!(eval_number("(/ 4)"), 0.25);
+    assert_eq!(eval_number("(/ 12 2 3)"), 2.0);
+
+    assert_eq!(eval_number("(mod -7 2)"), 1.0);
+    assert_eq!(eval_number("(mod 7 -2)"), -1.0);
+    assert_eq!(eval_number("(rem -7 2)"), -1.0);
+    assert_eq!(eval_number("(quot -7 2)"), -3.0);
+
+    let mut interp = Interpreter::new();
+    assert!(interp.eval_str("(-)").is_err());
+    assert!(interp.eval_str("(/ 1 0)").is_err());
+    assert!(interp.eval_str("(mod 1 0)").is_err());
+    assert!(interp.eval_str("(+ 1 \"2\")").is_err());
+}
+
+#[test]
+fn math_provides_numeric_functions() {
+    use lisp::interpreter::Interpreter;
+
+    assert_eq!(eval_number("pi"), std::f64::consts::PI);
+    assert_eq!(eval_number("e"), std::f64::consts::E);
+    assert_eq!(eval_number("(expt 2 10)"), 1024.0);
+    assert_eq!(eval_number("(sqrt 16)"), 4.0);
+    assert_eq!(eval_number("(log (exp 2))"), 2.0);
+    assert_eq!(eval_number("(log 8 2)"), 3.0);
+    assert_eq!(eval_number("(cos 0)"), 1.0);
+    assert_eq!(eval_number("(atan 1 1)"), std::f64::consts::FRAC_PI_4);
+
+    assert_eq!(eval_number("(floor -1.5)"), -2.0);
+    assert_eq!(eval_number("(ceil 1.2)"), 2.0);
+    assert_eq!(eval_number("(round 2.5)"), 2.0);
+    assert_eq!(eval_number("(round 3.5)"), 4.0);
+    assert_eq!(eval_number("(truncate -1.7)"), -1.0);
+    assert_eq!(eval_number("(abs -3)"), 3.0);
+    assert_eq!(eval_number("(min 3 1 2)"), 1.0);
+    assert_eq!(eval_number("(max 3 1 2)"), 3.0);
+
+    assert_eq!(eval_number("(gcd 12 18 -8)"), 2.0);
+    assert_eq!(eval_number("(lcm 4 6)"), 12.0);
+    assert_eq!(eval_number("(gcd)"), 0.0);
+    assert!(Interpreter::new().eval_str("(gcd 1.5 2)").is_err());
+}
+
+#[test]
+fn math_follows_ieee_rules() {
+    use lisp::interpreter::Interpreter;
+    use lisp::error::ErrorKind;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+    for code in &["(/ 1 0)", "(/ 0)", "(mod 1 0)", "(quot 1 0)"] {
+        let error = interp.eval_str(code).unwrap_err();
+        assert!(matches!(*error.kind(), ErrorKind::DivisionByZero), "{}", code);
     }
+
+    assert_eq!(eval_number("(* 1e308 10)"), f64::INFINITY);
+    assert_eq!(eval_number("(- +inf.0)"), f64::NEG_INFINITY);
+    assert!(eval_number("(- +inf.0 +inf.0)").is_nan());
+    assert!(eval_number("(sqrt -1)").is_nan());
+    assert!(eval_number("(min 1 +nan.0 2)").is_nan());
+    assert!(eval_number("(max +nan.0 1)").is_nan());
+
+    check(&mut interp, "(nan? (/ +inf.0 +inf.0))", RLType::True);
+    check(&mut interp, "(nan? 1)", RLType::False);
+    check(&mut interp, "(finite? 1)", RLType::True);
+    check(&mut interp, "(finite? -inf.0)", RLType::False);
+    check(&mut interp, "(infinite? -inf.0)", RLType::True);
+    check(&mut interp, "(= +nan.0 +nan.0)", RLType::False);
+    check(&mut interp, "(< 1 +nan.0)", RLType::False);
+    check(&mut interp, "(>= +nan.0 1)", RLType::False);
+    check(&mut interp, "(< -inf.0 0 +inf.0)", RLType::True);
+    check(&mut interp, "(pr-str (* 1e308 10) (- (* 1e308 10)) (sqrt -1))",
+          RLType::Str("+inf.0 -inf.0 +nan.0".to_string()));
 }
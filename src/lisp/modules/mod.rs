@@ -1,2 +1,22 @@
 pub mod comparison;
+pub mod base;
 pub mod math;
+pub mod types;
+pub mod string;
+pub mod format;
+pub mod chars;
+pub mod maps;
+pub mod vectors;
+pub mod sets;
+pub mod atoms;
+
+#[cfg(test)]
+use lisp::interpreter::Interpreter;
+#[cfg(test)]
+use lisp::types::RLType;
+
+// Evaluates code and checks its value, for the tests of the modules
+#[cfg(test)]
+pub fn check(interp: &mut Interpreter, code: &str, expected: RLType) {
+    assert_eq!(interp.eval_str(code).ok(), Some(expected), "{}", code);
+}
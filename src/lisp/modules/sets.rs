@@ -0,0 +1,164 @@
+// This module contains functions on hash sets. Sets are persistent values like maps: conj,
+// disj and the set operations return a new set and leave their arguments unchanged.
+
+use lisp::convert::IntoLisp;
+use lisp::env::Env;
+use lisp::persistent::PSet;
+use lisp::types::*;
+
+// Adds this module's functions to the provided environment
+pub fn add_module(env: &mut Env) {
+    let functions: &[(&str, Builtin)] = &[
+        ("hash-set", hash_set),
+        ("set", set),
+        ("set?", is_set),
+        ("disj", disj),
+        ("union", union),
+        ("intersection", intersection),
+        ("difference", difference),
+        ("subset?", is_subset),
+    ];
+    for &(name, function) in functions {
+        env.borrow_mut().insert(name.to_string(), RLType::Proc(function));
+    }
+}
+
+fn items(arg: &RLType) -> Result<&PSet<RLType>, RLError> {
+    match *arg {
+        RLType::Set(ref items) => Ok(items),
+        _ => Err(RLError::type_mismatch("set", arg)),
+    }
+}
+
+// The sets given as arguments, at least one
+fn sets<'a>(name: &str, args: &'a [RLType]) -> Result<Vec<&'a PSet<RLType>>, RLError> {
+    Arity::AtLeast(1).check(name, args.len())?;
+    args.iter().map(items).collect()
+}
+
+// Also called by the set literals read by the reader
+pub fn hash_set(args: Vec<RLType>) -> RLResult {
+    Ok(RLType::Set(args.into_iter().collect()))
+}
+
+// The set of the elements of a list, a vector or a set
+fn set(mut args: Vec<RLType>) -> RLResult {
+    Arity::Exact(1).check("set", args.len())?;
+    match args.remove(0) {
+        RLType::List(items) => Ok(RLType::Set(items.into_iter().collect())),
+        RLType::Vector(items) => Ok(RLType::Set(items.iter().cloned().collect())),
+        set @ RLType::Set(_) => Ok(set),
+        other => Err(RLError::type_mismatch("list, vector or set", &other)),
+    }
+}
+
+fn is_set(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(1).check("set?", args.len())?;
+    Ok(matches!(args[0], RLType::Set(_)).into_lisp())
+}
+
+// A new set with values added, called by conj
+pub fn conj(mut args: Vec<RLType>) -> RLResult {
+    let values = args.split_off(1);
+    let mut items = items(&args[0])?.clone();
+    items.extend(values);
+    Ok(RLType::Set(items))
+}
+
+fn disj(mut args: Vec<RLType>) -> RLResult {
+    Arity::AtLeast(1).check("disj", args.len())?;
+    let values = args.split_off(1);
+    let mut items = items(&args[0])?.clone();
+    for value in &values {
+        items.remove(value);
+    }
+    Ok(RLType::Set(items))
+}
+
+// True if the set holds the value, called by contains?
+pub fn contains(args: Vec<RLType>) -> RLResult {
+    Ok(items(&args[0])?.contains(&args[1]).into_lisp())
+}
+
+// The values in any of the sets, the first set is kept and the values of the others added
+fn union(args: Vec<RLType>) -> RLResult {
+    let sets = sets("union", &args)?;
+    let mut result = sets[0].clone();
+    for set in &sets[1..] {
+        result.extend(set.iter().cloned());
+    }
+    Ok(RLType::Set(result))
+}
+
+// The values in all the sets
+fn intersection(args: Vec<RLType>) -> RLResult {
+    let sets = sets("intersection", &args)?;
+    let result = sets[0].iter().filter(|value| sets[1..].iter().all(|set| set.contains(value)));
+    Ok(RLType::Set(result.cloned().collect()))
+}
+
+// The values in the first set and in none of the others
+fn difference(args: Vec<RLType>) -> RLResult {
+    let sets = sets("difference", &args)?;
+    let mut result = sets[0].clone();
+    for set in &sets[1..] {
+        for value in set.iter() {
+            result.remove(value);
+        }
+    }
+    Ok(RLType::Set(result))
+}
+
+// True if all the values of the first set are in the second one
+fn is_subset(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(2).check("subset?", args.len())?;
+    Ok(items(&args[0])?.is_subset(items(&args[1])?).into_lisp())
+}
+
+#[test]
+fn sets_literals_drop_duplicates() {
+    use lisp::interpreter::Interpreter;
+    use lisp::printer::pr_str;
+
+    let mut interp = Interpreter::new();
+    let value = interp.eval_str("#{:a (+ 1 1) 2 :a}").unwrap();
+    let text = pr_str(&value, true);
+    assert!(text == "#{:a 2}" || text == "#{2 :a}", "{}", text);
+    assert_eq!(interp.eval_str(&text).ok(), Some(value));
+    assert_eq!(interp.eval_str("(set? #{})").ok(), Some(RLType::True));
+    assert_eq!(interp.eval_str("(type-of #{})").ok(), Some(RLType::Symbol("set".to_string())));
+
+    let mut shadowed = Interpreter::new();
+    assert!(shadowed.eval_str("(def! hash-set (lambda (x) \"wrong\"))").is_ok());
+    assert_eq!(shadowed.eval_str("(set? #{1})").ok(), Some(RLType::True));
+
+    assert!(interp.eval_str("#{1 2").is_err());
+    assert!(interp.eval_str("#{1 2)").is_err());
+}
+
+#[test]
+fn sets_combine_into_new_sets() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+
+    assert!(interp.eval_str("(def! s #{1 2 3})").is_ok());
+    check(&mut interp, "(= (set (list 3 1 3 2)) s)", RLType::True);
+    check(&mut interp, "(= (set [1 2 2 3]) s)", RLType::True);
+    check(&mut interp, "(= (conj s 4 1) #{1 2 3 4})", RLType::True);
+    check(&mut interp, "(= (disj s 1 5) #{2 3})", RLType::True);
+    check(&mut interp, "(contains? s 2)", RLType::True);
+    check(&mut interp, "(contains? s 4)", RLType::False);
+    check(&mut interp, "(= s #{1 2 3})", RLType::True);
+    check(&mut interp, "(= (union s #{3 4} #{5}) #{1 2 3 4 5})", RLType::True);
+    check(&mut interp, "(= (intersection s #{2 3 4} #{3 2}) #{2 3})", RLType::True);
+    check(&mut interp, "(= (difference s #{1} #{3 7}) #{2})", RLType::True);
+    check(&mut interp, "(subset? #{1 3} s)", RLType::True);
+    check(&mut interp, "(subset? s #{1 3})", RLType::False);
+    check(&mut interp, "(get {#{1 2} :found} #{2 1})", RLType::Keyword(Keyword::new("found")));
+
+    assert!(interp.eval_str("(union s (list 1))").is_err());
+    assert!(interp.eval_str("(intersection)").is_err());
+    assert!(interp.eval_str("(set 1)").is_err());
+}
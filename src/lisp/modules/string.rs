@@ -0,0 +1,233 @@
+// This module contains functions on strings. Lengths and indexes count chars, not bytes.
+
+use lisp::convert::IntoLisp;
+use lisp::env::Env;
+use lisp::types::*;
+
+// Adds this module's functions to the provided environment
+pub fn add_module(env: &mut Env) {
+    let functions: &[(&str, Builtin)] = &[
+        ("string-length", length),
+        ("string-ref", char_at),
+        ("substring", substring),
+        ("string-append", append),
+        ("string-split", split),
+        ("string-join", join),
+        ("string-trim", trim),
+        ("upcase", upcase),
+        ("downcase", downcase),
+        ("string-index", index_of),
+        ("starts-with?", starts_with),
+        ("ends-with?", ends_with),
+        ("string-contains?", contains),
+        ("string-replace", replace),
+    ];
+    for &(name, function) in functions {
+        env.borrow_mut().insert(name.to_string(), RLType::Proc(function));
+    }
+}
+
+fn string(arg: &RLType) -> Result<&str, RLError> {
+    match *arg {
+        RLType::Str(ref text) => Ok(text),
+        _ => Err(RLError::type_mismatch("string", arg)),
+    }
+}
+
+// A position in a string of the given length in chars, the end of the string included
+fn index(arg: &RLType, length: usize) -> Result<usize, RLError> {
+    match *arg {
+        RLType::Number(n) if n.fract() == 0.0 && n >= 0.0 && n <= length as f64 => Ok(n as usize),
+        RLType::Number(n) if n.fract() == 0.0 => {
+            Err(RLError::new(ErrorKind::IndexOutOfRange { index: n, length }))
+        },
+        _ => Err(RLError::type_mismatch("index", arg)),
+    }
+}
+
+fn length(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(1).check("string-length", args.len())?;
+    Ok(string(&args[0])?.chars().count().into_lisp())
+}
+
+// The char at an index
+fn char_at(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(2).check("string-ref", args.len())?;
+    let text = string(&args[0])?;
+    let length = text.chars().count();
+
+    let i = index(&args[1], length)?;
+    match text.chars().nth(i) {
+        Some(c) => Ok(RLType::Char(c)),
+        None => Err(RLError::new(ErrorKind::IndexOutOfRange { index: i as f64, length })),
+    }
+}
+
+// The chars from start to end, or to the end of the string
+fn substring(args: Vec<RLType>) -> RLResult {
+    Arity::Range(2, 3).check("substring", args.len())?;
+    let text = string(&args[0])?;
+    let length = text.chars().count();
+
+    let start = index(&args[1], length)?;
+    let end = match args.get(2) {
+        Some(arg) => index(arg, length)?,
+        None => length,
+    };
+    if end < start {
+        return Err(RLError::new(ErrorKind::IndexOutOfRange { index: end as f64, length }));
+    }
+    Ok(RLType::Str(text.chars().skip(start).take(end - start).collect()))
+}
+
+fn append(args: Vec<RLType>) -> RLResult {
+    let mut result = String::new();
+    for arg in &args {
+        result.push_str(string(arg)?);
+    }
+    Ok(RLType::Str(result))
+}
+
+// The list of the parts separated by a separator, or by whitespace
+fn split(args: Vec<RLType>) -> RLResult {
+    Arity::Range(1, 2).check("string-split", args.len())?;
+    let text = string(&args[0])?;
+
+    let parts: Vec<&str> = match args.get(1) {
+        Some(arg) => {
+            let separator = string(arg)?;
+            if separator.is_empty() {
+                return Err(RLError::type_mismatch("non-empty string", arg));
+            }
+            text.split(separator).collect()
+        },
+        None => text.split_whitespace().collect(),
+    };
+    Ok(parts.into_lisp())
+}
+
+// Joins a list of strings, with an optional separator
+fn join(args: Vec<RLType>) -> RLResult {
+    Arity::Range(1, 2).check("string-join", args.len())?;
+    let items = match args[0] {
+        RLType::List(ref items) => items,
+        _ => return Err(RLError::type_mismatch("list", &args[0])),
+    };
+    let separator = match args.get(1) {
+        Some(arg) => string(arg)?,
+        None => "",
+    };
+
+    let parts = items.iter().map(string).collect::<Result<Vec<&str>, RLError>>()?;
+    Ok(RLType::Str(parts.join(separator)))
+}
+
+fn trim(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(1).check("string-trim", args.len())?;
+    Ok(RLType::Str(string(&args[0])?.trim().to_string()))
+}
+
+fn upcase(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(1).check("upcase", args.len())?;
+    Ok(RLType::Str(string(&args[0])?.to_uppercase()))
+}
+
+fn downcase(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(1).check("downcase", args.len())?;
+    Ok(RLType::Str(string(&args[0])?.to_lowercase()))
+}
+
+// The index in chars of the first occurrence of a string, #f if there is none
+fn index_of(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(2).check("string-index", args.len())?;
+    let text = string(&args[0])?;
+    let pattern = string(&args[1])?;
+
+    match text.find(pattern) {
+        Some(byte) => Ok(text[..byte].chars().count().into_lisp()),
+        None => Ok(RLType::False),
+    }
+}
+
+// Applies a test to the two string arguments
+fn test(name: &str, args: Vec<RLType>, test: fn(&str, &str) -> bool) -> RLResult {
+    Arity::Exact(2).check(name, args.len())?;
+    Ok(test(string(&args[0])?, string(&args[1])?).into_lisp())
+}
+
+fn starts_with(args: Vec<RLType>) -> RLResult {
+    test("starts-with?", args, |text, prefix| text.starts_with(prefix))
+}
+
+fn ends_with(args: Vec<RLType>) -> RLResult {
+    test("ends-with?", args, |text, suffix| text.ends_with(suffix))
+}
+
+fn contains(args: Vec<RLType>) -> RLResult {
+    test("string-contains?", args, |text, pattern| text.contains(pattern))
+}
+
+// Replaces every occurrence of a string
+fn replace(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(3).check("string-replace", args.len())?;
+    let text = string(&args[0])?;
+    let from = string(&args[1])?;
+    if from.is_empty() {
+        return Err(RLError::type_mismatch("non-empty string", &args[1]));
+    }
+    Ok(RLType::Str(text.replace(from, string(&args[2])?)))
+}
+
+#[test]
+fn string_functions_count_chars() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+    let string = |text: &str| RLType::Str(text.to_string());
+
+    check(&mut interp, "(string-length \"héllo→\")", RLType::Number(6.0));
+    check(&mut interp, "(string-ref \"héllo\" 1)", RLType::Char('é'));
+    check(&mut interp, "(substring \"héllo wörld\" 6)", string("wörld"));
+    check(&mut interp, "(substring \"héllo wörld\" 1 4)", string("éll"));
+    check(&mut interp, "(substring \"é\" 1 1)", string(""));
+    check(&mut interp, "(string-index \"→ä→b\" \"b\")", RLType::Number(3.0));
+    check(&mut interp, "(string-index \"abc\" \"z\")", RLType::False);
+    check(&mut interp, "(upcase \"straße\")", string("STRASSE"));
+    check(&mut interp, "(downcase \"ÀB\")", string("àb"));
+
+    match interp.eval_str("(string-ref \"é\" 1)").map_err(|e| e.kind().to_string()) {
+        Err(message) => assert_eq!(message, "Index 1 out of range for length 1"),
+        Ok(value) => panic!("got {:?}", value),
+    }
+    assert!(interp.eval_str("(substring \"abc\" 2 1)").is_err());
+    assert!(interp.eval_str("(substring \"abc\" 4)").is_err());
+    assert!(interp.eval_str("(substring \"abc\" -1)").is_err());
+    assert!(interp.eval_str("(substring \"abc\" 0.5)").is_err());
+}
+
+#[test]
+fn string_functions_build_and_search() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+    let string = |text: &str| RLType::Str(text.to_string());
+    let list = |items: &[&str]| RLType::List(items.iter().map(|s| string(s)).collect());
+
+    check(&mut interp, "(string-append \"a\" \"\" \"bc\")", string("abc"));
+    check(&mut interp, "(string-append)", string(""));
+    check(&mut interp, "(string-split \" a  b\\tc \")", list(&["a", "b", "c"]));
+    check(&mut interp, "(string-split \"a,b,,c\" \",\")", list(&["a", "b", "", "c"]));
+    check(&mut interp, "(string-join (list \"a\" \"b\" \"c\") \", \")", string("a, b, c"));
+    check(&mut interp, "(string-join (list \"a\" \"b\"))", string("ab"));
+    check(&mut interp, "(string-trim \"  a b \\n\")", string("a b"));
+    check(&mut interp, "(starts-with? \"hello\" \"he\")", RLType::True);
+    check(&mut interp, "(ends-with? \"hello\" \"he\")", RLType::False);
+    check(&mut interp, "(string-contains? \"hello\" \"ll\")", RLType::True);
+    check(&mut interp, "(string-replace \"a-b-c\" \"-\" \"+\")", string("a+b+c"));
+
+    assert!(interp.eval_str("(string-append \"a\" 1)").is_err());
+    assert!(interp.eval_str("(string-join (list \"a\" 1))").is_err());
+    assert!(interp.eval_str("(string-split \"abc\" \"\")").is_err());
+}
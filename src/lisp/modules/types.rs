@@ -0,0 +1,286 @@
+// This module contains type predicates and conversions between types
+
+use lisp::convert::IntoLisp;
+use lisp::env::Env;
+use lisp::lex::parse_number;
+use lisp::printer::pr_str;
+use lisp::types::*;
+
+// Adds this module's functions to the provided environment
+pub fn add_module(env: &mut Env) {
+    let functions: &[(&str, Builtin)] = &[
+        ("number?", is_number),
+        ("integer?", is_integer),
+        ("string?", is_string),
+        ("symbol?", is_symbol),
+        ("keyword?", is_keyword),
+        ("list?", is_list),
+        ("procedure?", is_procedure),
+        ("boolean?", is_boolean),
+        ("nil?", is_nil),
+        ("type-of", type_of),
+        ("number->string", number_to_string),
+        ("string->number", string_to_number),
+        ("symbol->string", symbol_to_string),
+        ("string->symbol", string_to_symbol),
+        ("list->string", list_to_string),
+        ("keyword", keyword),
+        ("name", name),
+    ];
+    for &(name, function) in functions {
+        env.borrow_mut().insert(name.to_string(), RLType::Proc(function));
+    }
+}
+
+// The only argument of a function
+fn single(name: &str, mut args: Vec<RLType>) -> RLResult {
+    if args.len() == 1 {
+        Ok(args.remove(0))
+    } else {
+        Err(RLError::arity(name, Arity::Exact(1), args.len()))
+    }
+}
+
+fn is_number(args: Vec<RLType>) -> RLResult {
+    Ok(matches!(single("number?", args)?, RLType::Number(_)).into_lisp())
+}
+
+// Numbers without a fractional part, infinities and NaN are not integers
+fn is_integer(args: Vec<RLType>) -> RLResult {
+    match single("integer?", args)? {
+        RLType::Number(n) => Ok((n.fract() == 0.0).into_lisp()),
+        _ => Ok(RLType::False),
+    }
+}
+
+fn is_string(args: Vec<RLType>) -> RLResult {
+    Ok(matches!(single("string?", args)?, RLType::Str(_)).into_lisp())
+}
+
+fn is_symbol(args: Vec<RLType>) -> RLResult {
+    Ok(matches!(single("symbol?", args)?, RLType::Symbol(_)).into_lisp())
+}
+
+fn is_keyword(args: Vec<RLType>) -> RLResult {
+    Ok(matches!(single("keyword?", args)?, RLType::Keyword(_)).into_lisp())
+}
+
+fn is_list(args: Vec<RLType>) -> RLResult {
+    Ok(matches!(single("list?", args)?, RLType::List(_)).into_lisp())
+}
+
+// Builtins and lambdas
+fn is_procedure(args: Vec<RLType>) -> RLResult {
+    Ok(single("procedure?", args)?.is_function().into_lisp())
+}
+
+fn is_boolean(args: Vec<RLType>) -> RLResult {
+    Ok(matches!(single("boolean?", args)?, RLType::True | RLType::False).into_lisp())
+}
+
+fn is_nil(args: Vec<RLType>) -> RLResult {
+    Ok(matches!(single("nil?", args)?, RLType::Nil).into_lisp())
+}
+
+// The name of the type of the argument as a symbol, as shown in error messages
+fn type_of(args: Vec<RLType>) -> RLResult {
+    let value = single("type-of", args)?;
+    Ok(RLType::Symbol(value.type_name().to_string()))
+}
+
+// The radix given as optional argument at index, 10 by default
+fn radix(args: &[RLType], index: usize) -> Result<u32, RLError> {
+    match args.get(index) {
+        None => Ok(10),
+        Some(&RLType::Number(n)) if n.fract() == 0.0 && (2.0..=36.0).contains(&n) => Ok(n as u32),
+        Some(other) => Err(RLError::type_mismatch("radix between 2 and 36", other)),
+    }
+}
+
+// The number as the reader would read it, integers can be written in another radix
+fn number_to_string(args: Vec<RLType>) -> RLResult {
+    Arity::Range(1, 2).check("number->string", args.len())?;
+
+    let radix = radix(&args, 1)?;
+    match args[0] {
+        RLType::Number(_) if radix == 10 => Ok(RLType::Str(pr_str(&args[0], true))),
+        RLType::Number(n) if n.fract() == 0.0 && n.abs() < 2f64.powi(53) => {
+            Ok(RLType::Str(format_radix(n as i64, radix)))
+        },
+        RLType::Number(_) => Err(RLError::type_mismatch("integer", &args[0])),
+        _ => Err(RLError::type_mismatch("number", &args[0])),
+    }
+}
+
+fn format_radix(n: i64, radix: u32) -> String {
+    let mut digits = Vec::new();
+    let mut rest = n.unsigned_abs();
+    loop {
+        digits.push(::std::char::from_digit((rest % radix as u64) as u32, radix).unwrap());
+        rest /= radix as u64;
+        if rest == 0 {
+            break;
+        }
+    }
+    if n < 0 {
+        digits.push('-');
+    }
+    digits.iter().rev().collect()
+}
+
+// The number written in the string, #f if it is not a number
+fn string_to_number(args: Vec<RLType>) -> RLResult {
+    Arity::Range(1, 2).check("string->number", args.len())?;
+
+    let radix = radix(&args, 1)?;
+    let text = match args[0] {
+        RLType::Str(ref text) => text.trim(),
+        _ => return Err(RLError::type_mismatch("string", &args[0])),
+    };
+
+    let number = if radix == 10 {
+        parse_number(text)
+    } else {
+        i64::from_str_radix(text, radix).ok().map(|n| RLType::Number(n as f64))
+    };
+    Ok(number.unwrap_or(RLType::False))
+}
+
+fn symbol_to_string(args: Vec<RLType>) -> RLResult {
+    match single("symbol->string", args)? {
+        RLType::Symbol(name) => Ok(RLType::Str(name)),
+        other => Err(RLError::type_mismatch("symbol", &other)),
+    }
+}
+
+fn string_to_symbol(args: Vec<RLType>) -> RLResult {
+    match single("string->symbol", args)? {
+        RLType::Str(text) => Ok(RLType::Symbol(text)),
+        other => Err(RLError::type_mismatch("string", &other)),
+    }
+}
+
+// Concatenates a list of chars and strings
+fn list_to_string(args: Vec<RLType>) -> RLResult {
+    let items = match single("list->string", args)? {
+        RLType::List(items) => items,
+        other => return Err(RLError::type_mismatch("list", &other)),
+    };
+
+    let mut text = String::new();
+    for item in items {
+        match item {
+            RLType::Char(c) => text.push(c),
+            RLType::Str(ref part) => text.push_str(part),
+            other => return Err(RLError::type_mismatch("char or string", &other)),
+        }
+    }
+    Ok(RLType::Str(text))
+}
+
+// The keyword with the name of a string or a symbol, without a colon
+fn keyword(args: Vec<RLType>) -> RLResult {
+    match single("keyword", args)? {
+        RLType::Str(ref name) | RLType::Symbol(ref name) if !name.is_empty() => {
+            Ok(RLType::Keyword(Keyword::new(name)))
+        },
+        keyword @ RLType::Keyword(_) => Ok(keyword),
+        other => Err(RLError::type_mismatch("non-empty string or symbol", &other)),
+    }
+}
+
+// The name of a keyword or a symbol as a string, a keyword's name has no colon. A string is
+// its own name.
+fn name(args: Vec<RLType>) -> RLResult {
+    match single("name", args)? {
+        RLType::Keyword(ref keyword) => Ok(RLType::Str(keyword.name().to_string())),
+        RLType::Symbol(name) | RLType::Str(name) => Ok(RLType::Str(name)),
+        other => Err(RLError::type_mismatch("keyword, symbol or string", &other)),
+    }
+}
+
+#[test]
+fn types_tests_and_names_types() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+
+    check(&mut interp, "(number? 1.5)", RLType::True);
+    check(&mut interp, "(number? \"1\")", RLType::False);
+    check(&mut interp, "(integer? 2)", RLType::True);
+    check(&mut interp, "(integer? 2.5)", RLType::False);
+    check(&mut interp, "(integer? +inf.0)", RLType::False);
+    check(&mut interp, "(integer? \"2\")", RLType::False);
+    check(&mut interp, "(string? \"a\")", RLType::True);
+    check(&mut interp, "(symbol? (string->symbol \"a\"))", RLType::True);
+    check(&mut interp, "(list? (list))", RLType::True);
+    check(&mut interp, "(list? nil)", RLType::False);
+    check(&mut interp, "(procedure? +)", RLType::True);
+    check(&mut interp, "(procedure? (lambda (x) x))", RLType::True);
+    check(&mut interp, "(procedure? 1)", RLType::False);
+    check(&mut interp, "(boolean? #f)", RLType::True);
+    check(&mut interp, "(boolean? nil)", RLType::False);
+    check(&mut interp, "(nil? nil)", RLType::True);
+    check(&mut interp, "(nil? (list))", RLType::False);
+
+    check(&mut interp, "(type-of 1)", RLType::Symbol("number".to_string()));
+    check(&mut interp, "(type-of (list 1))", RLType::Symbol("list".to_string()));
+    check(&mut interp, "(type-of +)", RLType::Symbol("procedure".to_string()));
+    check(&mut interp, "(type-of (lambda (x) x))", RLType::Symbol("lambda".to_string()));
+    assert!(interp.eval_str("(number?)").is_err());
+    assert!(interp.eval_str("(string? 1 2)").is_err());
+}
+
+#[test]
+fn types_converts_values() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+    let string = |text: &str| RLType::Str(text.to_string());
+
+    check(&mut interp, "(number->string 42)", string("42"));
+    check(&mut interp, "(number->string -1.5)", string("-1.5"));
+    check(&mut interp, "(number->string (- +inf.0))", string("-inf.0"));
+    check(&mut interp, "(number->string -255 16)", string("-ff"));
+    check(&mut interp, "(number->string 5 2)", string("101"));
+    check(&mut interp, "(string->number \"42\")", RLType::Number(42.0));
+    check(&mut interp, "(string->number \" -1e2 \")", RLType::Number(-100.0));
+    check(&mut interp, "(string->number \"ff\" 16)", RLType::Number(255.0));
+    check(&mut interp, "(string->number \"abc\")", RLType::False);
+    check(&mut interp, "(string->number \"inf\")", RLType::False);
+    check(&mut interp, "(symbol->string (string->symbol \"a b\"))", string("a b"));
+    check(&mut interp, "(list->string (list \"a\" \"bc\"))", string("abc"));
+    check(&mut interp, "(list->string (list #\\a \"bc\"))", string("abc"));
+
+    assert!(interp.eval_str("(number->string 1.5 2)").is_err());
+    assert!(interp.eval_str("(number->string 1 37)").is_err());
+    assert!(interp.eval_str("(symbol->string \"a\")").is_err());
+    assert!(interp.eval_str("(list->string (list 1))").is_err());
+}
+
+#[test]
+fn types_keywords_evaluate_to_themselves() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+
+    check(&mut interp, ":name", RLType::Keyword(Keyword::new("name")));
+    check(&mut interp, "(keyword? :a)", RLType::True);
+    check(&mut interp, "(keyword? (string->symbol \":a\"))", RLType::False);
+    check(&mut interp, "(keyword? \"a\")", RLType::False);
+    check(&mut interp, "(= (keyword \"a\") :a)", RLType::True);
+    check(&mut interp, "(eq? (keyword (string->symbol \"a\")) :a)", RLType::True);
+    check(&mut interp, "(= :a :b)", RLType::False);
+    check(&mut interp, "(name :config-file)", RLType::Str("config-file".to_string()));
+    check(&mut interp, "(name (string->symbol \"x\"))", RLType::Str("x".to_string()));
+    check(&mut interp, "(name \"text\")", RLType::Str("text".to_string()));
+    check(&mut interp, "(type-of :a)", RLType::Symbol("keyword".to_string()));
+    check(&mut interp, "(get {:port 80 :host \"localhost\"} :port)", RLType::Number(80.0));
+    check(&mut interp, "(pr-str {:a :b})", RLType::Str("{:a :b}".to_string()));
+
+    assert!(interp.eval_str("(keyword \"\")").is_err());
+    assert!(interp.eval_str("(name 1)").is_err());
+}
@@ -0,0 +1,282 @@
+// This module contains functions on vectors. Vectors are persistent: conj and assoc return a
+// new vector sharing most of its structure with their argument, which is left unchanged, and
+// so do vector-set and vector-push. Indexing, conj and assoc take a time logarithmic in base 32,
+// which is constant in practice.
+
+use lisp::convert::IntoLisp;
+use lisp::env::Env;
+use lisp::modules::sets;
+use lisp::persistent::PVec;
+use lisp::types::*;
+
+// Adds this module's functions to the provided environment
+pub fn add_module(env: &mut Env) {
+    let functions: &[(&str, Builtin)] = &[
+        ("vector", vector),
+        ("vector?", is_vector),
+        ("vector-length", length),
+        ("vector-ref", get),
+        ("vector-set", set),
+        ("vector-push", push),
+        ("conj", conj),
+        ("subvec", subvec),
+        ("vector->list", vector_to_list),
+        ("list->vector", list_to_vector),
+    ];
+    for &(name, function) in functions {
+        env.borrow_mut().insert(name.to_string(), RLType::Proc(function));
+    }
+}
+
+fn items(arg: &RLType) -> Result<&PVec<RLType>, RLError> {
+    match *arg {
+        RLType::Vector(ref items) => Ok(items),
+        _ => Err(RLError::type_mismatch("vector", arg)),
+    }
+}
+
+// A position in a vector of the given length, the end of the vector included
+fn index(arg: &RLType, length: usize) -> Result<usize, RLError> {
+    match *arg {
+        RLType::Number(n) if n.fract() == 0.0 && n >= 0.0 && n <= length as f64 => Ok(n as usize),
+        RLType::Number(n) if n.fract() == 0.0 => {
+            Err(RLError::new(ErrorKind::IndexOutOfRange { index: n, length }))
+        },
+        _ => Err(RLError::type_mismatch("index", arg)),
+    }
+}
+
+// The position of an element, which must be before the end of the vector
+fn element_index(arg: &RLType, length: usize) -> Result<usize, RLError> {
+    match index(arg, length)? {
+        i if i == length => {
+            Err(RLError::new(ErrorKind::IndexOutOfRange { index: i as f64, length }))
+        },
+        i => Ok(i),
+    }
+}
+
+// Also called by the vector literals read by the reader
+pub fn vector(args: Vec<RLType>) -> RLResult {
+    Ok(RLType::Vector(args.into_iter().collect()))
+}
+
+fn is_vector(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(1).check("vector?", args.len())?;
+    Ok(matches!(args[0], RLType::Vector(_)).into_lisp())
+}
+
+fn length(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(1).check("vector-length", args.len())?;
+    Ok(items(&args[0])?.len().into_lisp())
+}
+
+fn get(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(2).check("vector-ref", args.len())?;
+    let items = items(&args[0])?;
+    let i = element_index(&args[1], items.len())?;
+    Ok(items.get(i).unwrap().clone())
+}
+
+// A new vector with the element at an index replaced
+fn set(mut args: Vec<RLType>) -> RLResult {
+    Arity::Exact(3).check("vector-set", args.len())?;
+    let value = args.pop().unwrap();
+    let mut items = items(&args[0])?.clone();
+    let i = element_index(&args[1], items.len())?;
+    items.set(i, value);
+    Ok(RLType::Vector(items))
+}
+
+// A new vector with values added at the end
+fn push(mut args: Vec<RLType>) -> RLResult {
+    Arity::AtLeast(2).check("vector-push", args.len())?;
+    let values = args.split_off(1);
+    let mut items = items(&args[0])?.clone();
+    items.extend(values);
+    Ok(RLType::Vector(items))
+}
+
+// A new vector with the elements from start to end, or to the end of the vector
+fn subvec(args: Vec<RLType>) -> RLResult {
+    Arity::Range(2, 3).check("subvec", args.len())?;
+    let items = items(&args[0])?;
+    let length = items.len();
+
+    let start = index(&args[1], length)?;
+    let end = match args.get(2) {
+        Some(arg) => index(arg, length)?,
+        None => length,
+    };
+    if end < start {
+        return Err(RLError::new(ErrorKind::IndexOutOfRange { index: end as f64, length }));
+    }
+    Ok(RLType::Vector(items.iter().skip(start).take(end - start).cloned().collect()))
+}
+
+// A new vector with values added at the end, or a new set with values added
+fn conj(mut args: Vec<RLType>) -> RLResult {
+    Arity::AtLeast(1).check("conj", args.len())?;
+    if let RLType::Set(_) = args[0] {
+        return sets::conj(args);
+    }
+    let values = args.split_off(1);
+    let mut items = items(&args[0])?.clone();
+    items.extend(values);
+    Ok(RLType::Vector(items))
+}
+
+// (assoc vector index value ...) is a new vector with the elements at the indexes replaced.
+// The index can be the length, to add an element at the end.
+pub fn assoc(mut args: Vec<RLType>) -> RLResult {
+    Arity::AtLeast(3).check("assoc", args.len())?;
+    let values = args.split_off(1);
+    if !values.len().is_multiple_of(2) {
+        return error("assoc: expected a value for each index".to_string());
+    }
+    let mut items = items(&args[0])?.clone();
+
+    let mut values = values.into_iter();
+    while let (Some(index), Some(value)) = (values.next(), values.next()) {
+        match self::index(&index, items.len())? {
+            i if i == items.len() => items.push(value),
+            i => {
+                items.set(i, value);
+            },
+        }
+    }
+    Ok(RLType::Vector(items))
+}
+
+fn vector_to_list(args: Vec<RLType>) -> RLResult {
+    Arity::Exact(1).check("vector->list", args.len())?;
+    Ok(RLType::List(items(&args[0])?.iter().cloned().collect()))
+}
+
+fn list_to_vector(mut args: Vec<RLType>) -> RLResult {
+    Arity::Exact(1).check("list->vector", args.len())?;
+    match args.remove(0) {
+        RLType::List(items) => Ok(RLType::Vector(items.into_iter().collect())),
+        other => Err(RLError::type_mismatch("list", &other)),
+    }
+}
+
+#[test]
+fn vectors_literals_evaluate_their_elements() {
+    use lisp::interpreter::Interpreter;
+    use lisp::printer::pr_str;
+
+    let mut interp = Interpreter::new();
+    let value = interp.eval_str("[1 (+ 1 1) \"three\" [4]]").unwrap();
+    assert_eq!(pr_str(&value, true), "[1 2 \"three\" [4]]");
+    assert_eq!(interp.eval_str(&pr_str(&value, true)).ok(), Some(value));
+    assert_eq!(interp.eval_str("(vector? [])").ok(), Some(RLType::True));
+    assert_eq!(interp.eval_str("(type-of [])").ok(), Some(RLType::Symbol("vector".to_string())));
+
+    let mut shadowed = Interpreter::new();
+    assert!(shadowed.eval_str("(def! vector (lambda (x) \"wrong\"))").is_ok());
+    assert_eq!(shadowed.eval_str("(vector? [1])").ok(), Some(RLType::True));
+
+    assert!(interp.eval_str("[1 2)").is_err());
+    assert!(interp.eval_str("(1 2]").is_err());
+}
+
+#[test]
+fn vectors_are_indexed_and_set_into_new_vectors() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+    let number = RLType::Number;
+
+    assert!(interp.eval_str("(def! v [10 20 30])").is_ok());
+    assert!(interp.eval_str("(def! same v)").is_ok());
+    check(&mut interp, "(vector-ref v 1)", number(20.0));
+    check(&mut interp, "(vector-length v)", number(3.0));
+    check(&mut interp, "(= (vector-set [1 2] 0 5) [5 2])", RLType::True);
+    check(&mut interp, "(= (vector-push [1] 2 3) [1 2 3])", RLType::True);
+    assert!(interp.eval_str("(def! v (vector-set v 1 \"b\"))").is_ok());
+    assert!(interp.eval_str("(def! v (vector-push v 40 50))").is_ok());
+    check(&mut interp, "(vector->list v)", RLType::List(vec![
+        number(10.0), RLType::Str("b".to_string()), number(30.0), number(40.0), number(50.0),
+    ]));
+    check(&mut interp, "(= same [10 20 30])", RLType::True);
+    check(&mut interp, "(= (subvec v 3) [40 50])", RLType::True);
+    check(&mut interp, "(= (subvec v 1 2) [\"b\"])", RLType::True);
+    check(&mut interp, "(= (list->vector (list 1 2)) [1 2])", RLType::True);
+    check(&mut interp, "(eq? v same)", RLType::False);
+    assert!(interp.eval_str("(def! same v)").is_ok());
+    check(&mut interp, "(eq? v same)", RLType::True);
+    check(&mut interp, "(eq? [1] [1])", RLType::False);
+    check(&mut interp, "(equal? [1] [1])", RLType::True);
+    check(&mut interp, "(equal? [1] (list 1))", RLType::False);
+
+    // vector-set and vector-push are functions like the others
+    check(&mut interp, "(procedure? vector-set)", RLType::True);
+    assert!(interp.eval_str("(def! call (lambda (f x y) (f x y)))").is_ok());
+    check(&mut interp, "(= (call vector-push [1] 2) [1 2])", RLType::True);
+
+    match interp.eval_str("(vector-ref v 5)").map_err(|e| e.kind().to_string()) {
+        Err(message) => assert_eq!(message, "Index 5 out of range for length 5"),
+        Ok(value) => panic!("got {:?}", value),
+    }
+    match interp.eval_str("(vector-ref [1 2] 1e300)").map_err(|e| e.kind().to_string()) {
+        Err(message) => assert_eq!(message, format!("Index {} out of range for length 2", 1e300)),
+        Ok(value) => panic!("got {:?}", value),
+    }
+    assert!(interp.eval_str("(vector-set v -1 0)").is_err());
+    assert!(interp.eval_str("(vector-set v 5 0)").is_err());
+    assert!(interp.eval_str("(subvec v 2 1)").is_err());
+    assert!(interp.eval_str("(vector-ref (list 1) 0)").is_err());
+    assert!(interp.eval_str("(vector-push (list 1) 2)").is_err());
+    assert!(interp.eval_str("(vector-push [1])").is_err());
+    assert!(interp.eval_str("(vector-set v 0)").is_err());
+}
+
+#[test]
+fn vectors_used_as_keys_are_not_changed_by_vector_push() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+    let string = |text: &str| RLType::Str(text.to_string());
+
+    assert!(interp.eval_str("(def! a [1 2])").is_ok());
+    assert!(interp.eval_str("(def! m {a \"x\"})").is_ok());
+    assert!(interp.eval_str("(def! s #{a})").is_ok());
+    assert!(interp.eval_str("(def! a (vector-push a 3))").is_ok());
+    check(&mut interp, "(get m [1 2])", string("x"));
+    check(&mut interp, "(get m a)", RLType::Nil);
+    check(&mut interp, "(contains? s [1 2])", RLType::True);
+
+    // a vector pushed into itself holds its previous value, it can't contain itself
+    assert!(interp.eval_str("(def! v [1])").is_ok());
+    assert!(interp.eval_str("(def! v (vector-push v v))").is_ok());
+    check(&mut interp, "(pr-str v)", string("[1 [1]]"));
+    check(&mut interp, "(= v [1 [1]])", RLType::True);
+}
+
+#[test]
+fn vectors_conj_and_assoc_leave_their_argument_unchanged() {
+    use lisp::interpreter::Interpreter;
+    use lisp::modules::check;
+
+    let mut interp = Interpreter::new();
+
+    let big: PVec<RLType> = (0..100_000).map(|i| RLType::Number(i as f64)).collect();
+    interp.env().borrow_mut().insert("big".to_string(), RLType::Vector(big));
+    assert!(interp.eval_str("(def! more (conj big -1 -2))").is_ok());
+    assert!(interp.eval_str("(def! changed (assoc big 5 \"five\" 100000 \"end\"))").is_ok());
+
+    check(&mut interp, "(vector-length big)", RLType::Number(100_000.0));
+    check(&mut interp, "(vector-length more)", RLType::Number(100_002.0));
+    check(&mut interp, "(vector-ref more 100001)", RLType::Number(-2.0));
+    check(&mut interp, "(vector-ref big 5)", RLType::Number(5.0));
+    check(&mut interp, "(vector-ref changed 5)", RLType::Str("five".to_string()));
+    check(&mut interp, "(vector-ref changed 100000)", RLType::Str("end".to_string()));
+    check(&mut interp, "(= (assoc [1 2] 0 3) [3 2])", RLType::True);
+
+    assert!(interp.eval_str("(assoc [1 2] 3 0)").is_err());
+    assert!(interp.eval_str("(assoc [1 2] 0)").is_err());
+    assert!(interp.eval_str("(conj (list) 1)").is_err());
+}
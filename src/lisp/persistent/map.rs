@@ -0,0 +1,410 @@
+// A persistent hash map: a hash array mapped trie. Each branch uses 5 bits of the hash of the
+// keys to pick one of 32 slots, only the used slots are stored and a bitmap tells which ones.
+// Keys whose hashes are equal end up in a collision node. Nodes are shared between copies,
+// cloning is constant time and insert or remove copy only the nodes on the path to the key,
+// when they are shared with another map.
+
+use std::borrow::Borrow;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::{self, Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::rc::Rc;
+
+const BITS: u32 = 5;
+const MASK: u64 = (1 << BITS) - 1;
+
+#[derive(Clone)]
+enum Entry<K, V> {
+    Pair(u64, K, V),
+    Node(Rc<Node<K, V>>),
+}
+
+#[derive(Clone)]
+enum Node<K, V> {
+    Branch { bitmap: u32, entries: Vec<Entry<K, V>> },
+    // only holds pairs, all with the same hash
+    Collision { hash: u64, entries: Vec<Entry<K, V>> },
+}
+
+#[derive(Clone)]
+pub struct PMap<K, V> {
+    len: usize,
+    root: Rc<Node<K, V>>,
+}
+
+fn hash_of<K: Hash + ?Sized>(key: &K) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+// The bit of the slot of a hash at shift, and the position of the slot among the used ones
+fn slot(bitmap: u32, hash: u64, shift: u32) -> (u32, usize) {
+    let bit = 1 << ((hash >> shift) & MASK);
+    (bit, (bitmap & (bit - 1)).count_ones() as usize)
+}
+
+impl<K: Clone + Hash + Eq, V: Clone> PMap<K, V> {
+    pub fn new() -> PMap<K, V> {
+        PMap {
+            len: 0,
+            root: Rc::new(Node::Branch { bitmap: 0, entries: Vec::new() }),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // True if both are copies of the same map, without comparing their entries
+    pub fn ptr_eq(&self, other: &PMap<K, V>) -> bool {
+        Rc::ptr_eq(&self.root, &other.root)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>, Q: Hash + Eq + ?Sized
+    {
+        let hash = hash_of(key);
+        let mut node = &*self.root;
+        let mut shift = 0;
+        loop {
+            match *node {
+                Node::Branch { bitmap, ref entries } => {
+                    let (bit, position) = slot(bitmap, hash, shift);
+                    if bitmap & bit == 0 {
+                        return None;
+                    }
+                    match entries[position] {
+                        Entry::Pair(h, ref k, ref v) if h == hash && k.borrow() == key => {
+                            return Some(v)
+                        },
+                        Entry::Pair(..) => return None,
+                        Entry::Node(ref child) => node = child,
+                    }
+                },
+                Node::Collision { ref entries, .. } => {
+                    return entries.iter().find_map(|entry| match *entry {
+                        Entry::Pair(h, ref k, ref v) if h == hash && k.borrow() == key => Some(v),
+                        _ => None,
+                    });
+                },
+            }
+            shift += BITS;
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>, Q: Hash + Eq + ?Sized
+    {
+        self.get(key).is_some()
+    }
+
+    // Sets the value of a key, returns the previous one
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = hash_of(&key);
+        let previous = insert_in(Rc::make_mut(&mut self.root), 0, hash, key, value);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    // Removes a key, returns its value
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>, Q: Hash + Eq + ?Sized
+    {
+        // nothing is copied when the key is missing
+        if !self.contains_key(key) {
+            return None;
+        }
+        let removed = remove_in(Rc::make_mut(&mut self.root), 0, hash_of(key), key);
+        self.len -= 1;
+        removed
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { stack: vec![entries(&self.root).iter()], left: self.len }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+fn entries<K, V>(node: &Node<K, V>) -> &[Entry<K, V>] {
+    match *node {
+        Node::Branch { ref entries, .. } | Node::Collision { ref entries, .. } => entries,
+    }
+}
+
+fn insert_in<K, V>(node: &mut Node<K, V>, shift: u32, hash: u64, key: K, value: V) -> Option<V>
+    where K: Clone + Hash + Eq, V: Clone
+{
+    match *node {
+        Node::Branch { ref mut bitmap, ref mut entries } => {
+            let (bit, position) = slot(*bitmap, hash, shift);
+            if *bitmap & bit == 0 {
+                *bitmap |= bit;
+                entries.insert(position, Entry::Pair(hash, key, value));
+                return None;
+            }
+
+            let entry = &mut entries[position];
+            match *entry {
+                Entry::Pair(h, ref k, ref mut v) if h == hash && *k == key => {
+                    return Some(::std::mem::replace(v, value));
+                },
+                Entry::Node(ref mut child) => {
+                    return insert_in(Rc::make_mut(child), shift + BITS, hash, key, value);
+                },
+                Entry::Pair(..) => (),
+            }
+            // two keys in the same slot, they move to a new node
+            let existing = ::std::mem::replace(entry, Entry::Node(Rc::new(Node::Branch {
+                bitmap: 0,
+                entries: Vec::new(),
+            })));
+            let pair = Entry::Pair(hash, key, value);
+            *entry = Entry::Node(Rc::new(pair_node(shift + BITS, existing, pair)));
+            None
+        },
+        Node::Collision { hash: shared, ref mut entries } if shared == hash => {
+            for entry in entries.iter_mut() {
+                if let Entry::Pair(_, ref k, ref mut v) = *entry {
+                    if *k == key {
+                        return Some(::std::mem::replace(v, value));
+                    }
+                }
+            }
+            entries.push(Entry::Pair(hash, key, value));
+            None
+        },
+        Node::Collision { hash: shared, .. } => {
+            // a different hash with the same first bits, the collision moves below a branch
+            let empty = Node::Branch { bitmap: 0, entries: Vec::new() };
+            let collision = ::std::mem::replace(node, empty);
+            let (bit, _) = slot(0, shared, shift);
+            *node = Node::Branch { bitmap: bit, entries: vec![Entry::Node(Rc::new(collision))] };
+            insert_in(node, shift, hash, key, value)
+        },
+    }
+}
+
+// A node holding two pairs whose hashes have the same bits before shift
+fn pair_node<K, V>(shift: u32, first: Entry<K, V>, second: Entry<K, V>) -> Node<K, V> {
+    let (h1, h2) = match (&first, &second) {
+        (&Entry::Pair(h1, ..), &Entry::Pair(h2, ..)) => (h1, h2),
+        _ => unreachable!("only pairs are moved to a new node"),
+    };
+    if h1 == h2 {
+        return Node::Collision { hash: h1, entries: vec![first, second] };
+    }
+
+    let (bit1, _) = slot(0, h1, shift);
+    let (bit2, _) = slot(0, h2, shift);
+    if bit1 == bit2 {
+        let child = pair_node(shift + BITS, first, second);
+        Node::Branch { bitmap: bit1, entries: vec![Entry::Node(Rc::new(child))] }
+    } else if bit1 < bit2 {
+        Node::Branch { bitmap: bit1 | bit2, entries: vec![first, second] }
+    } else {
+        Node::Branch { bitmap: bit1 | bit2, entries: vec![second, first] }
+    }
+}
+
+// Removes a key that is in the node. Nodes left with a single pair are replaced by the pair.
+fn remove_in<K, V, Q>(node: &mut Node<K, V>, shift: u32, hash: u64, key: &Q) -> Option<V>
+    where K: Clone + Hash + Eq + Borrow<Q>, V: Clone, Q: Hash + Eq + ?Sized
+{
+    match *node {
+        Node::Branch { ref mut bitmap, ref mut entries } => {
+            let (bit, position) = slot(*bitmap, hash, shift);
+            let (removed, single) = match entries[position] {
+                Entry::Node(ref mut child) => {
+                    let child = Rc::make_mut(child);
+                    let removed = remove_in(child, shift + BITS, hash, key);
+                    (removed, single_pair(child))
+                },
+                Entry::Pair(..) => {
+                    *bitmap &= !bit;
+                    return match entries.remove(position) {
+                        Entry::Pair(_, _, value) => Some(value),
+                        Entry::Node(_) => None,
+                    };
+                },
+            };
+            if let Some(pair) = single {
+                entries[position] = pair;
+            }
+            removed
+        },
+        Node::Collision { ref mut entries, .. } => {
+            let position = entries.iter().position(|entry| match *entry {
+                Entry::Pair(_, ref k, _) => k.borrow() == key,
+                Entry::Node(_) => false,
+            })?;
+            match entries.remove(position) {
+                Entry::Pair(_, _, value) => Some(value),
+                Entry::Node(_) => None,
+            }
+        },
+    }
+}
+
+// Takes the pair out of a node that holds nothing else
+fn single_pair<K, V>(node: &mut Node<K, V>) -> Option<Entry<K, V>> {
+    match *node {
+        Node::Branch { ref mut entries, .. } | Node::Collision { ref mut entries, .. }
+            if entries.len() == 1 && matches!(entries[0], Entry::Pair(..)) => entries.pop(),
+        _ => None,
+    }
+}
+
+impl<K: Clone + Hash + Eq, V: Clone> Default for PMap<K, V> {
+    fn default() -> PMap<K, V> {
+        PMap::new()
+    }
+}
+
+// Goes through the entries depth first, in the order of the hashes
+pub struct Iter<'a, K: 'a, V: 'a> {
+    stack: Vec<::std::slice::Iter<'a, Entry<K, V>>>,
+    left: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        loop {
+            let next = self.stack.last_mut()?.next();
+            match next {
+                Some(Entry::Pair(_, key, value)) => {
+                    self.left -= 1;
+                    return Some((key, value));
+                },
+                Some(Entry::Node(child)) => self.stack.push(entries(child).iter()),
+                None => {
+                    self.stack.pop();
+                },
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.left, Some(self.left))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<'a, K: Clone + Hash + Eq, V: Clone> IntoIterator for &'a PMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K: Clone + Hash + Eq, V: Clone> FromIterator<(K, V)> for PMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(entries: I) -> PMap<K, V> {
+        let mut map = PMap::new();
+        map.extend(entries);
+        map
+    }
+}
+
+impl<K: Clone + Hash + Eq, V: Clone> Extend<(K, V)> for PMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, entries: I) {
+        for (key, value) in entries {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K: Clone + Hash + Eq, V: Clone + PartialEq> PartialEq for PMap<K, V> {
+    fn eq(&self, other: &PMap<K, V>) -> bool {
+        self.len == other.len && self.iter().all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K: Clone + Hash + Eq + Debug, V: Clone + Debug> Debug for PMap<K, V> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[test]
+fn pmap_matches_a_hash_map() {
+    use std::collections::HashMap;
+
+    let mut map = PMap::new();
+    let mut expected = HashMap::new();
+    for i in 0..20_000u64 {
+        let key = i.wrapping_mul(0x9e37_79b9_7f4a_7c15) % 5000;
+        if i % 3 == 0 {
+            assert_eq!(map.remove(&key), expected.remove(&key));
+        } else {
+            assert_eq!(map.insert(key, i), expected.insert(key, i));
+        }
+    }
+
+    assert_eq!(map.len(), expected.len());
+    assert_eq!(map.iter().len(), expected.len());
+    assert!(map.iter().all(|(key, value)| expected.get(key) == Some(value)));
+    assert!((0..5000).all(|key| map.get(&key) == expected.get(&key)));
+}
+
+#[test]
+fn pmap_copies_are_unchanged() {
+    let original: PMap<String, usize> = (0..1000).map(|i| (i.to_string(), i)).collect();
+    let mut copy = original.clone();
+    copy.insert("10".to_string(), 0);
+    copy.remove("20");
+    copy.insert("new".to_string(), 1);
+
+    assert_eq!(original.len(), 1000);
+    assert_eq!(original.get("10"), Some(&10));
+    assert_eq!(original.get("20"), Some(&20));
+    assert_eq!(original.get("new"), None);
+    assert_eq!(copy.get("10"), Some(&0));
+    assert_eq!(copy.get("20"), None);
+    assert!(original != copy);
+}
+
+#[test]
+fn pmap_handles_equal_hashes() {
+    // keys hashed on their first char only
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct Key(&'static str);
+
+    impl Hash for Key {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.0.as_bytes()[0].hash(state);
+        }
+    }
+
+    let mut map = PMap::new();
+    for &name in &["apple", "avocado", "banana", "apricot", "blueberry"] {
+        map.insert(Key(name), name.len());
+    }
+    assert_eq!(map.len(), 5);
+    assert_eq!(map.get(&Key("avocado")), Some(&7));
+    assert_eq!(map.get(&Key("almond")), None);
+
+    assert_eq!(map.remove(&Key("apple")), Some(5));
+    assert_eq!(map.remove(&Key("avocado")), Some(7));
+    assert_eq!(map.get(&Key("apricot")), Some(&7));
+    assert_eq!(map.get(&Key("blueberry")), Some(&9));
+    assert_eq!(map.iter().count(), 3);
+}
@@ -0,0 +1,9 @@
+// Persistent collections: copies share their structure, so cloning is cheap and changing a
+// copy leaves the others as they were
+pub mod map;
+pub mod set;
+pub mod vector;
+
+pub use self::map::PMap;
+pub use self::set::PSet;
+pub use self::vector::PVec;
@@ -0,0 +1,135 @@
+// A persistent hash set: a persistent map whose values are all ()
+
+use std::borrow::Borrow;
+use std::fmt::{self, Debug, Formatter};
+use std::hash::Hash;
+use std::iter::FromIterator;
+
+use lisp::persistent::map::{self, PMap};
+
+#[derive(Clone)]
+pub struct PSet<T> {
+    map: PMap<T, ()>,
+}
+
+impl<T: Clone + Hash + Eq> PSet<T> {
+    pub fn new() -> PSet<T> {
+        PSet { map: PMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    // True if both are copies of the same set, without comparing their values
+    pub fn ptr_eq(&self, other: &PSet<T>) -> bool {
+        self.map.ptr_eq(&other.map)
+    }
+
+    pub fn contains<Q>(&self, value: &Q) -> bool
+        where T: Borrow<Q>, Q: Hash + Eq + ?Sized
+    {
+        self.map.contains_key(value)
+    }
+
+    // Adds a value, returns false if it was already in the set
+    pub fn insert(&mut self, value: T) -> bool {
+        self.map.insert(value, ()).is_none()
+    }
+
+    // Removes a value, returns false if it was not in the set
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+        where T: Borrow<Q>, Q: Hash + Eq + ?Sized
+    {
+        self.map.remove(value).is_some()
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { entries: self.map.iter() }
+    }
+
+    pub fn is_subset(&self, other: &PSet<T>) -> bool {
+        self.len() <= other.len() && self.iter().all(|value| other.contains(value))
+    }
+}
+
+impl<T: Clone + Hash + Eq> Default for PSet<T> {
+    fn default() -> PSet<T> {
+        PSet::new()
+    }
+}
+
+pub struct Iter<'a, T: 'a> {
+    entries: map::Iter<'a, T, ()>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.entries.next().map(|(value, _)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T: Clone + Hash + Eq> IntoIterator for &'a PSet<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T: Clone + Hash + Eq> FromIterator<T> for PSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(values: I) -> PSet<T> {
+        let mut set = PSet::new();
+        set.extend(values);
+        set
+    }
+}
+
+impl<T: Clone + Hash + Eq> Extend<T> for PSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+        for value in values {
+            self.insert(value);
+        }
+    }
+}
+
+impl<T: Clone + Hash + Eq> PartialEq for PSet<T> {
+    fn eq(&self, other: &PSet<T>) -> bool {
+        self.len() == other.len() && self.is_subset(other)
+    }
+}
+
+impl<T: Clone + Hash + Eq + Debug> Debug for PSet<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+#[test]
+fn pset_keeps_one_of_each_value() {
+    let mut set: PSet<u32> = vec![3, 1, 3, 2, 1].into_iter().collect();
+    let copy = set.clone();
+    assert_eq!(set.len(), 3);
+    assert!(!set.insert(2));
+    assert!(set.insert(4));
+    assert!(set.remove(&1));
+    assert!(!set.remove(&1));
+
+    assert!(set.contains(&4) && !set.contains(&1));
+    assert!(copy.contains(&1) && !copy.contains(&4));
+    assert!(!copy.is_subset(&set));
+    assert!(set == vec![2, 3, 4].into_iter().collect());
+}
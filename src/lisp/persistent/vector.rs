@@ -0,0 +1,275 @@
+// A persistent vector: a trie with 32 children per node, the leaves holding the elements in
+// order, plus a tail holding the last elements until it is full and pushed in the trie.
+// Nodes are shared between copies, cloning is constant time and set or push copy only the
+// nodes on the path to the element, when they are shared with another vector.
+
+use std::fmt::{self, Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::rc::Rc;
+
+const BITS: usize = 5;
+const WIDTH: usize = 1 << BITS;
+const MASK: usize = WIDTH - 1;
+
+#[derive(Clone)]
+enum Node<T> {
+    Branch(Vec<Rc<Node<T>>>),
+    Leaf(Vec<T>),
+}
+
+#[derive(Clone)]
+pub struct PVec<T> {
+    len: usize,
+    // the number of index bits below the root, BITS for a root whose children are leaves
+    shift: usize,
+    root: Rc<Node<T>>,
+    tail: Rc<Vec<T>>,
+}
+
+impl<T: Clone> PVec<T> {
+    pub fn new() -> PVec<T> {
+        PVec {
+            len: 0,
+            shift: BITS,
+            root: Rc::new(Node::Branch(Vec::new())),
+            tail: Rc::new(Vec::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // True if both are copies of the same vector, without comparing their elements
+    pub fn ptr_eq(&self, other: &PVec<T>) -> bool {
+        Rc::ptr_eq(&self.root, &other.root) && Rc::ptr_eq(&self.tail, &other.tail)
+    }
+
+    // The index of the first element in the tail
+    fn tail_offset(&self) -> usize {
+        self.len - self.tail.len()
+    }
+
+    // The leaf or the tail holding the element at index, which must be in range
+    fn chunk(&self, index: usize) -> &[T] {
+        if index >= self.tail_offset() {
+            return &self.tail;
+        }
+
+        let mut node = &*self.root;
+        let mut level = self.shift;
+        loop {
+            match *node {
+                Node::Branch(ref children) => {
+                    node = &children[(index >> level) & MASK];
+                    level -= BITS;
+                },
+                Node::Leaf(ref items) => return items,
+            }
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index < self.len {
+            Some(&self.chunk(index)[index & MASK])
+        } else {
+            None
+        }
+    }
+
+    // Replaces the element at index, returns false if the index is out of range
+    pub fn set(&mut self, index: usize, value: T) -> bool {
+        if index >= self.len {
+            return false;
+        }
+        let offset = self.tail_offset();
+        if index >= offset {
+            Rc::make_mut(&mut self.tail)[index - offset] = value;
+        } else {
+            set_in(&mut self.root, self.shift, index, value);
+        }
+        true
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.tail.len() == WIDTH {
+            let leaf = Rc::new(Node::Leaf(self.tail.as_ref().clone()));
+            self.tail = Rc::new(Vec::with_capacity(WIDTH));
+
+            // the root is full when the trie holds WIDTH << shift elements
+            let in_trie = self.len - WIDTH;
+            if in_trie >> BITS == 1 << self.shift {
+                let path = new_path(self.shift, leaf);
+                self.root = Rc::new(Node::Branch(vec![self.root.clone(), path]));
+                self.shift += BITS;
+            } else {
+                push_leaf(&mut self.root, self.shift, in_trie, leaf);
+            }
+        }
+        Rc::make_mut(&mut self.tail).push(value);
+        self.len += 1;
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { vector: self, index: 0, chunk: [].iter() }
+    }
+}
+
+fn set_in<T: Clone>(node: &mut Rc<Node<T>>, level: usize, index: usize, value: T) {
+    match *Rc::make_mut(node) {
+        Node::Branch(ref mut children) => {
+            set_in(&mut children[(index >> level) & MASK], level - BITS, index, value)
+        },
+        Node::Leaf(ref mut items) => items[index & MASK] = value,
+    }
+}
+
+// A branch for each level down to the leaf
+fn new_path<T>(level: usize, leaf: Rc<Node<T>>) -> Rc<Node<T>> {
+    if level == 0 {
+        leaf
+    } else {
+        Rc::new(Node::Branch(vec![new_path(level - BITS, leaf)]))
+    }
+}
+
+// Adds a leaf after the first index elements of the trie below node
+fn push_leaf<T: Clone>(node: &mut Rc<Node<T>>, level: usize, index: usize, leaf: Rc<Node<T>>) {
+    if let Node::Branch(ref mut children) = *Rc::make_mut(node) {
+        let slot = (index >> level) & MASK;
+        if level == BITS {
+            children.push(leaf);
+        } else if slot < children.len() {
+            push_leaf(&mut children[slot], level - BITS, index, leaf);
+        } else {
+            children.push(new_path(level - BITS, leaf));
+        }
+    }
+}
+
+impl<T: Clone> Default for PVec<T> {
+    fn default() -> PVec<T> {
+        PVec::new()
+    }
+}
+
+// Goes through the elements a leaf at a time
+pub struct Iter<'a, T: 'a> {
+    vector: &'a PVec<T>,
+    index: usize,
+    chunk: ::std::slice::Iter<'a, T>,
+}
+
+impl<'a, T: Clone> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if let Some(item) = self.chunk.next() {
+            return Some(item);
+        }
+        if self.index >= self.vector.len {
+            return None;
+        }
+
+        self.chunk = self.vector.chunk(self.index).iter();
+        self.index += self.chunk.len();
+        self.chunk.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.vector.len - self.index + self.chunk.len();
+        (left, Some(left))
+    }
+}
+
+impl<'a, T: Clone> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T: Clone> IntoIterator for &'a PVec<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T: Clone> FromIterator<T> for PVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(items: I) -> PVec<T> {
+        let mut vector = PVec::new();
+        vector.extend(items);
+        vector
+    }
+}
+
+impl<T: Clone> Extend<T> for PVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        for item in items {
+            self.push(item);
+        }
+    }
+}
+
+impl<T: Clone + PartialEq> PartialEq for PVec<T> {
+    fn eq(&self, other: &PVec<T>) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Clone + Hash> Hash for PVec<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        for item in self {
+            item.hash(state);
+        }
+    }
+}
+
+impl<T: Clone + Debug> Debug for PVec<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[test]
+fn pvec_matches_a_vec() {
+    let mut vector = PVec::new();
+    let mut expected = Vec::new();
+    // enough elements for a trie of three levels
+    for i in 0..40_000 {
+        vector.push(i);
+        expected.push(i);
+    }
+    for i in (0..40_000).step_by(7) {
+        assert!(vector.set(i, i * 2));
+        expected[i] = i * 2;
+    }
+
+    assert_eq!(vector.len(), expected.len());
+    assert!(vector.iter().eq(expected.iter()));
+    assert_eq!(vector.iter().len(), expected.len());
+    assert!((0..40_000).all(|i| vector.get(i) == Some(&expected[i])));
+    assert_eq!(vector.get(40_000), None);
+    assert!(!vector.set(40_000, 0));
+}
+
+#[test]
+fn pvec_copies_are_unchanged() {
+    let original: PVec<usize> = (0..1000).collect();
+    let mut copy = original.clone();
+    copy.set(10, 0);
+    copy.set(999, 0);
+    copy.push(1000);
+
+    assert_eq!(original.len(), 1000);
+    assert_eq!(original.get(10), Some(&10));
+    assert_eq!(original.get(999), Some(&999));
+    assert_eq!(copy.get(10), Some(&0));
+    assert_eq!(copy.get(1000), Some(&1000));
+    assert!(original != copy);
+    assert!(original == (0..1000).collect());
+}
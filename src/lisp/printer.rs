@@ -0,0 +1,363 @@
+// Printing of values. The readable form (pr-str, Debug) can be read back by the reader, with
+// strings quoted and escaped. The display form (str, Display) is meant for people and prints
+// strings as they are. Pretty printing puts lists that fit in the width on one line and
+// breaks the others with one element per line, aligned after the opening paren. Maps are
+// broken the same way with one entry per line, and vectors and sets like lists.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use lisp::lex::CHAR_NAMES;
+use lisp::persistent::PMap;
+use lisp::types::RLType;
+
+// The width used by Display and the REPL
+pub const DEFAULT_WIDTH: usize = 80;
+
+thread_local! {
+    // The atoms whose value is being printed, an atom can hold itself
+    static PRINTING: RefCell<Vec<*const RefCell<RLType>>> = const { RefCell::new(Vec::new()) };
+}
+
+// Prints value on a single line
+pub fn pr_str(value: &RLType, readably: bool) -> String {
+    match *value {
+        RLType::Nil => "nil".to_string(),
+        RLType::True => "#t".to_string(),
+        RLType::False => "#f".to_string(),
+        RLType::Symbol(ref name) => name.clone(),
+        RLType::Keyword(ref keyword) => format!(":{}", keyword.name()),
+        RLType::Str(ref text) if readably => escape(text),
+        RLType::Str(ref text) => text.clone(),
+        RLType::Char(c) if readably => print_char(c),
+        RLType::Char(c) => c.to_string(),
+        RLType::Number(number) => print_number(number),
+        RLType::Proc(_) => "#<procedure>".to_string(),
+        RLType::Native(ref native) => format!("#<procedure {}>", native.name),
+        RLType::Lambda(ref closure) => match closure.name {
+            Some(ref name) => format!("#<lambda {}>", name),
+            None => "#<lambda>".to_string(),
+        },
+        RLType::List(ref items) => {
+            let items: Vec<String> = items.iter().map(|item| pr_str(item, readably)).collect();
+            format!("({})", items.join(" "))
+        },
+        RLType::Vector(ref items) => {
+            let items: Vec<String> = items.iter().map(|item| pr_str(item, readably)).collect();
+            format!("[{}]", items.join(" "))
+        },
+        RLType::Set(ref items) => {
+            let items: Vec<String> = items.iter().map(|item| pr_str(item, readably)).collect();
+            format!("#{{{}}}", items.join(" "))
+        },
+        RLType::Atom(ref cell) => print_atom(cell, readably),
+        RLType::Map(ref entries) => {
+            let entries: Vec<String> = entries.iter()
+                .map(|(key, value)| {
+                    format!("{} {}", pr_str(key, readably), pr_str(value, readably))
+                })
+                .collect();
+            format!("{{{}}}", entries.join(" "))
+        },
+    }
+}
+
+// Prints value on lines of at most width columns, when its scalar values allow it
+pub fn pretty(value: &RLType, width: usize, readably: bool) -> String {
+    let mut out = String::new();
+    print(value, 0, 0, width, readably, &mut out);
+    out
+}
+
+// An atom with its value. An atom found again in its own value is printed without it, which
+// would be printed endlessly.
+fn print_atom(cell: &Rc<RefCell<RLType>>, readably: bool) -> String {
+    let pointer = Rc::as_ptr(cell);
+    if PRINTING.with(|printing| printing.borrow().contains(&pointer)) {
+        return "#<atom ...>".to_string();
+    }
+    PRINTING.with(|printing| printing.borrow_mut().push(pointer));
+    let value = pr_str(&cell.borrow(), readably);
+    PRINTING.with(|printing| printing.borrow_mut().pop());
+    format!("#<atom {}>", value)
+}
+
+// Numbers as read by the reader, which has its own syntax for infinities and NaN
+fn print_number(number: f64) -> String {
+    if number.is_nan() {
+        "+nan.0".to_string()
+    } else if number.is_infinite() {
+        if number > 0.0 { "+inf.0" } else { "-inf.0" }.to_string()
+    } else {
+        number.to_string()
+    }
+}
+
+// A char literal, using the names known by the reader and code points for control chars
+fn print_char(c: char) -> String {
+    match CHAR_NAMES.iter().find(|&&(_, named)| named == c) {
+        Some(&(name, _)) => format!("#\\{}", name),
+        None if c.is_control() => format!("#\\x{:x}", c as u32),
+        None => format!("#\\{}", c),
+    }
+}
+
+// A string literal for text, with the escape sequences understood by the reader
+fn escape(text: &str) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\r' => literal.push_str("\\r"),
+            '\0' => literal.push_str("\\0"),
+            _ => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+// Prints value starting at column indent, followed by trailing closing parens
+fn print(value: &RLType, indent: usize, trailing: usize, width: usize, readably: bool,
+         out: &mut String) {
+    let line = pr_str(value, readably);
+    if indent + line.chars().count() + trailing <= width {
+        return out.push_str(&line);
+    }
+    match *value {
+        RLType::List(ref items) => {
+            print_items(items.iter().collect(), ("(", ")"), indent, trailing, width, readably, out)
+        },
+        RLType::Vector(ref items) => {
+            print_items(items.iter().collect(), ("[", "]"), indent, trailing, width, readably, out)
+        },
+        RLType::Set(ref items) => {
+            print_items(items.iter().collect(), ("#{", "}"), indent, trailing, width, readably, out)
+        },
+        RLType::Map(ref entries) => print_entries(entries, indent, trailing, width, readably, out),
+        _ => out.push_str(&line),
+    }
+}
+
+// Prints the items of a list, a vector or a set one per line, between the delimiters
+fn print_items(items: Vec<&RLType>, delimiters: (&str, &str), indent: usize, trailing: usize,
+               width: usize, readably: bool, out: &mut String) {
+    let (open, close) = delimiters;
+    let indent = indent + open.len();
+    out.push_str(open);
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent));
+        }
+        let closing = if i + 1 == items.len() { trailing + close.len() } else { 0 };
+        print(item, indent, closing, width, readably, out);
+    }
+    out.push_str(close);
+}
+
+// Prints the entries of a map one per line, the value after the key on the same line
+fn print_entries(entries: &PMap<RLType, RLType>, indent: usize, trailing: usize, width: usize,
+                 readably: bool, out: &mut String) {
+    out.push('{');
+    for (i, (key, value)) in entries.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent + 1));
+        }
+        let key = pr_str(key, readably);
+        out.push_str(&key);
+        out.push(' ');
+        let closing = if i + 1 == entries.len() { trailing + 1 } else { 0 };
+        print(value, indent + 2 + key.chars().count(), closing, width, readably, out);
+    }
+    out.push('}');
+}
+
+// Structural comparison of the values produced by the reader or the evaluator, numbers by their
+// bits so that NaN is the same as itself
+#[cfg(test)]
+fn same(a: &RLType, b: &RLType) -> bool {
+    match (a, b) {
+        (RLType::Nil, RLType::Nil) | (RLType::True, RLType::True) |
+        (RLType::False, RLType::False) => true,
+        (RLType::Symbol(a), RLType::Symbol(b)) | (RLType::Str(a), RLType::Str(b)) => a == b,
+        (RLType::Char(a), RLType::Char(b)) => a == b,
+        (RLType::Keyword(a), RLType::Keyword(b)) => a == b,
+        (RLType::Number(a), RLType::Number(b)) => a.to_bits() == b.to_bits(),
+        (RLType::List(a), RLType::List(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+        },
+        (RLType::Vector(a), RLType::Vector(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same(a, b))
+        },
+        (RLType::Map(a), RLType::Map(b)) => {
+            a.len() == b.len() && a.iter().all(|(k, v)| b.get(k).is_some_and(|w| same(v, w)))
+        },
+        (RLType::Set(a), RLType::Set(b)) => a.len() == b.len() && a.iter().all(|v| b.contains(v)),
+        _ => false,
+    }
+}
+
+// A xorshift generator, enough to produce varied values from a seed
+#[cfg(test)]
+struct Random(u64);
+
+#[cfg(test)]
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick(&mut self, chars: &[char], max_len: usize) -> String {
+        let len = self.below(max_len + 1);
+        (0..len).map(|_| chars[self.below(chars.len())]).collect()
+    }
+
+    // A value that the reader can produce, lists are nested up to depth levels
+    fn value(&mut self, depth: usize) -> RLType {
+        use lisp::types::Keyword;
+
+        let kinds = if depth == 0 { 6 } else { 7 };
+        match self.below(kinds) {
+            0 => RLType::Nil,
+            1 => if self.below(2) == 0 { RLType::True } else { RLType::False },
+            2 if self.below(10) == 0 => {
+                let special = [f64::INFINITY, f64::NEG_INFINITY, f64::NAN];
+                RLType::Number(special[self.below(3)])
+            },
+            2 => {
+                let number = self.next() as i64 as f64 / (1u64 << self.below(40)) as f64;
+                RLType::Number(number)
+            },
+            3 => {
+                let chars: Vec<char> = "ab Z\"\\\n\t\r\0();#é→".chars().collect();
+                RLType::Str(self.pick(&chars, 8))
+            },
+            4 if self.below(2) == 0 => {
+                let chars: Vec<char> = " a(\"\\;#\n\t\u{1}é→x".chars().collect();
+                RLType::Char(chars[self.below(chars.len())])
+            },
+            4 | 5 => {
+                let chars: Vec<char> = "abcxyz-+*!?<>=_é".chars().collect();
+                let name = format!("s{}", self.pick(&chars, 6));
+                if self.below(3) == 0 {
+                    RLType::Keyword(Keyword::new(&name))
+                } else {
+                    RLType::Symbol(name)
+                }
+            },
+            _ => {
+                let len = self.below(5);
+                RLType::List((0..len).map(|_| self.value(depth - 1)).collect())
+            },
+        }
+    }
+
+    // A value whose readable form evaluates to the same value: the scalars of value that
+    // evaluate to themselves, and vectors, maps and sets of them nested up to depth levels
+    fn literal(&mut self, depth: usize) -> RLType {
+        use lisp::types::Keyword;
+
+        let len = self.below(5);
+        match self.below(if depth == 0 { 1 } else { 4 }) {
+            0 => match self.value(0) {
+                RLType::Symbol(name) => RLType::Keyword(Keyword::new(&name)),
+                // NaN is not equal to itself, so it could not be found in a map or a set
+                RLType::Number(n) if n.is_nan() => RLType::Number(f64::INFINITY),
+                scalar => scalar,
+            },
+            1 => RLType::Vector((0..len).map(|_| self.literal(depth - 1)).collect()),
+            2 => {
+                let mut entry = || (self.literal(depth - 1), self.literal(depth - 1));
+                RLType::Map((0..len).map(|_| entry()).collect())
+            },
+            _ => RLType::Set((0..len).map(|_| self.literal(depth - 1)).collect()),
+        }
+    }
+}
+
+#[test]
+fn printer_round_trips_through_the_reader() {
+    use lisp::lex::parse_all;
+
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    for _ in 0..500 {
+        let value = random.value(4);
+
+        let text = pr_str(&value, true);
+        let read = parse_all(&text).unwrap();
+        assert_eq!(read.len(), 1, "{}", text);
+        assert!(same(&read[0], &value), "{} was read as {}", text, pr_str(&read[0], true));
+
+        let text = pretty(&value, 20, true);
+        assert!(same(&parse_all(&text).unwrap()[0], &value), "{}", text);
+    }
+}
+
+#[test]
+fn printer_round_trips_collections_through_evaluation() {
+    use lisp::interpreter::Interpreter;
+
+    // vectors, maps and sets are read back as the calls building them, which evaluate to the
+    // printed value when their elements evaluate to themselves
+    let mut interp = Interpreter::new();
+    let mut random = Random(0x9e37_79b9_7f4a_7c15);
+    for _ in 0..500 {
+        let value = random.literal(3);
+        for text in &[pr_str(&value, true), pretty(&value, 20, true)] {
+            let evaluated = interp.eval_str(text).unwrap();
+            assert!(same(&evaluated, &value), "{} evaluated to {}", text, pr_str(&evaluated, true));
+        }
+    }
+}
+
+#[test]
+fn printer_displays_strings_raw() {
+    let value = RLType::List(vec![RLType::Str("a \"b\"\n".to_string()),
+                                  RLType::Symbol("c".to_string())]);
+
+    assert_eq!(pr_str(&value, true), "(\"a \\\"b\\\"\\n\" c)");
+    assert_eq!(pr_str(&value, false), "(a \"b\"\n c)");
+    assert_eq!(format!("{:?}", value), pr_str(&value, true));
+    assert_eq!(format!("{}", value), pr_str(&value, false));
+}
+
+#[test]
+fn printer_breaks_long_lists() {
+    use lisp::lex::parse_all;
+
+    let value = parse_all("(1 (2 3) (4 (5 6)))").unwrap().remove(0);
+    assert_eq!(pretty(&value, 80, true), "(1 (2 3) (4 (5 6)))");
+    assert_eq!(pretty(&value, 14, true), "(1\n (2 3)\n (4 (5 6)))");
+    assert_eq!(pretty(&value, 10, true), "(1\n (2 3)\n (4\n  (5 6)))");
+    assert_eq!(pretty(&RLType::List(vec![]), 0, true), "()");
+}
+
+#[test]
+fn printer_prints_maps_as_literals() {
+    use lisp::interpreter::Interpreter;
+
+    let mut interp = Interpreter::new();
+    let value = interp.eval_str("{\"a\" (list 1 2) 3 {}}").unwrap();
+    let text = pr_str(&value, true);
+    assert!(text == "{\"a\" (1 2) 3 {}}" || text == "{3 {} \"a\" (1 2)}", "{}", text);
+
+    // the keys and values of the literal are evaluated, (1 2) needs to be built with list
+    let value = interp.eval_str("{\"key\" \"a long value\" \"other\" 42}").unwrap();
+    let text = pretty(&value, 20, true);
+    assert!(text == "{\"key\" \"a long value\"\n \"other\" 42}" ||
+            text == "{\"other\" 42\n \"key\" \"a long value\"}", "{}", text);
+    assert_eq!(interp.eval_str(&text).ok(), Some(value));
+}
@@ -1,85 +1,270 @@
 use std;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
 use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::rc::Rc;
+
+use lisp::context::Context;
+use lisp::env::Env;
+use lisp::eval::apply;
+use lisp::persistent::{PMap, PSet, PVec};
+use lisp::printer;
+
+pub use lisp::error::{RLError, ErrorKind, error};
 
 // The RLType (RustLisp) enum wraps all possible values in the language.
-// It can be atomic (a string, function, number or one of the default values),
-// or a list of other RLTypes, internally represented with a Vec
+// It can be atomic (a symbol, string, function, number or one of the default values),
+// a list of other RLTypes, internally represented with a Vec, a map between RLTypes, a set or
+// a vector. Maps, sets and vectors are persistent collections, which are cheap to clone.
+// Atoms hold a single value that can be replaced, they are shared by the values that hold them.
 #[derive(Clone)]
 pub enum RLType {
     Nil,
     True,
     False,
     Symbol(String),
-    Number(f32),
-    Proc(fn(Vec<RLType>) -> RLResult),
+    Keyword(Keyword),
+    Str(String),
+    Char(char),
+    Number(f64),
+    Proc(Builtin),
+    Native(RLNative),
     Lambda(RLClosure),
     List(Vec<RLType>),
+    Map(PMap<RLType, RLType>),
+    Set(PSet<RLType>),
+    Vector(PVec<RLType>),
+    Atom(Rc<RefCell<RLType>>),
+}
+
+// The name of a keyword such as :name. Names are interned, so that keywords are compared by
+// pointer and cloning one doesn't copy its name.
+#[derive(Clone)]
+pub struct Keyword(Rc<str>);
+
+thread_local! {
+    static KEYWORDS: RefCell<HashSet<Rc<str>>> = RefCell::new(HashSet::new());
+}
+
+impl Keyword {
+    pub fn new(name: &str) -> Keyword {
+        KEYWORDS.with(|keywords| {
+            let mut keywords = keywords.borrow_mut();
+            if let Some(interned) = keywords.get(name) {
+                return Keyword(interned.clone());
+            }
+            let interned: Rc<str> = Rc::from(name);
+            keywords.insert(interned.clone());
+            Keyword(interned)
+        })
+    }
+
+    // The name without the colon
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Keyword {
+    fn eq(&self, other: &Keyword) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
 }
 
 #[derive(Clone)]
 pub struct RLClosure {
-    pub ast: Box<RLType>,
+    // shared by the copies of the lambda, which eq? tells apart from other lambdas
+    pub ast: Rc<RLType>,
     pub bindings: Vec<String>,
+    // set when the lambda is bound with def!, used in error traces
+    pub name: Option<String>,
 }
 
-pub enum RLError {
-    Message(String),
-    InvalidValue(RLType),
+// The number of arguments accepted by a native function
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+    Range(usize, usize),
 }
 
-impl RLError {
-    pub fn get_message(&self) -> String {
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
         match *self {
-            RLError::Message(ref s) => s.to_string(),
-            RLError::InvalidValue(ref v) => format!("{}", v),
+            Arity::Exact(n) => count == n,
+            Arity::AtLeast(min) => count >= min,
+            Arity::Range(min, max) => count >= min && count <= max,
+        }
+    }
+
+    // Fails with an arity error naming the function when count is not accepted
+    pub fn check(&self, name: &str, count: usize) -> Result<(), RLError> {
+        if self.accepts(count) {
+            Ok(())
+        } else {
+            Err(RLError::arity(name, *self, count))
         }
     }
 }
 
-pub type RLResult = Result<RLType, RLError>;
+impl Display for Arity {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match *self {
+            Arity::Exact(n) => write!(f, "{}", n),
+            Arity::AtLeast(min) => write!(f, "at least {}", min),
+            Arity::Range(min, max) => write!(f, "{} to {}", min, max),
+        }
+    }
+}
+
+// A builtin implemented by a plain Rust function
+pub type Builtin = fn(Vec<RLType>) -> RLResult;
+
+pub type NativeFn = Rc<dyn Fn(&mut Context, Vec<RLType>) -> RLResult>;
 
-pub fn error(message: String) -> RLResult {
-    Err(RLError::Message(message.to_string()))
+// A builtin implemented by a Rust closure, which can capture state from the host
+// application. The arity is checked before calling it.
+#[derive(Clone)]
+pub struct RLNative {
+    pub name: String,
+    pub arity: Arity,
+    pub func: NativeFn,
+}
+
+impl RLNative {
+    pub fn new<F>(name: &str, arity: Arity, func: F) -> RLNative
+        where F: Fn(&mut Context, Vec<RLType>) -> RLResult + 'static
+    {
+        RLNative {
+            name: name.to_string(),
+            arity,
+            func: Rc::new(func),
+        }
+    }
+
+    pub fn call(&self, ctx: &mut Context, args: Vec<RLType>) -> RLResult {
+        self.arity.check(&self.name, args.len())?;
+        (self.func)(ctx, args)
+    }
 }
 
+pub type RLResult = Result<RLType, RLError>;
+
+// The readable form, which the reader can parse back
 impl Debug for RLType {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        match *self {
-            RLType::Symbol(ref name) => write!(f, "\"{}\"", name),
-            RLType::Number(num) => write!(f, "{}", num),
-            RLType::List(ref tokens) => write!(f, "{:?}", tokens),
-            RLType::Proc(_) => write!(f, "proc"),
-            RLType::Lambda(_) => write!(f, "lambda"),
-            RLType::True => write!(f, "#t"),
-            RLType::False => write!(f, "#f"),
-            RLType::Nil => write!(f, "nil"),
-        }
+        write!(f, "{}", printer::pr_str(self, true))
     }
 }
 
-// TODO: display/debug should show different things ideally
+// The form shown to people, with strings printed raw and long lists broken over lines
 impl Display for RLType {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}", printer::pretty(self, printer::DEFAULT_WIDTH, false))
+    }
+}
+
+// Structural equality: non-collection values compare by value, lists element by element.
+// Builtins are equal when they are the same function, lambdas when they have the same
+// parameters and body. Atom cells are equal only to themselves, as their value can change.
+// Numbers follow IEEE rules, so NaN is not equal to itself.
+impl PartialEq for RLType {
+    fn eq(&self, other: &RLType) -> bool {
+        match (self, other) {
+            (RLType::Nil, RLType::Nil) |
+            (RLType::True, RLType::True) |
+            (RLType::False, RLType::False) => true,
+            (RLType::Symbol(a), RLType::Symbol(b)) => a == b,
+            (RLType::Keyword(a), RLType::Keyword(b)) => a == b,
+            (RLType::Str(a), RLType::Str(b)) => a == b,
+            (RLType::Char(a), RLType::Char(b)) => a == b,
+            (RLType::Number(a), RLType::Number(b)) => a == b,
+            (RLType::Proc(a), RLType::Proc(b)) => std::ptr::fn_addr_eq(*a, *b),
+            (RLType::Native(a), RLType::Native(b)) => Rc::ptr_eq(&a.func, &b.func),
+            (RLType::Lambda(a), RLType::Lambda(b)) => a.bindings == b.bindings && a.ast == b.ast,
+            (RLType::List(a), RLType::List(b)) => a == b,
+            (RLType::Map(a), RLType::Map(b)) => a == b,
+            (RLType::Set(a), RLType::Set(b)) => a == b,
+            (RLType::Atom(a), RLType::Atom(b)) => Rc::ptr_eq(a, b),
+            (RLType::Vector(a), RLType::Vector(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+// Needed for map keys. NaN breaks reflexivity, so a NaN key can't be found again.
+impl Eq for RLType {}
+
+// Consistent with PartialEq: equal values have the same hash
+impl Hash for RLType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
         match *self {
-            RLType::Symbol(ref name) => write!(f, "\"{}\"", name),
-            RLType::Number(number) => write!(f, "{}", number),
-            RLType::List(ref tokens) => write!(f, "{:?}", tokens),
-            RLType::Proc(_) => write!(f, "proc"),
-            RLType::Lambda(_) => write!(f, "lambda"),
-            RLType::True => write!(f, "#t"),
-            RLType::False => write!(f, "#f"),
-            RLType::Nil => write!(f, "nil"),
+            RLType::Nil | RLType::True | RLType::False => (),
+            RLType::Symbol(ref text) | RLType::Str(ref text) => text.hash(state),
+            RLType::Keyword(ref keyword) => keyword.name().hash(state),
+            RLType::Char(c) => c.hash(state),
+            // 0.0 and -0.0 are equal
+            RLType::Number(n) => (if n == 0.0 { 0.0f64 } else { n }).to_bits().hash(state),
+            RLType::Proc(func) => func.hash(state),
+            RLType::Native(ref native) => (Rc::as_ptr(&native.func) as *const ()).hash(state),
+            RLType::Lambda(ref lambda) => {
+                lambda.bindings.hash(state);
+                lambda.ast.hash(state);
+            },
+            RLType::List(ref items) => items.hash(state),
+            RLType::Vector(ref items) => items.hash(state),
+            RLType::Map(ref entries) => hash_unordered(entries.iter(), state),
+            RLType::Set(ref items) => hash_unordered(items.iter(), state),
+            RLType::Atom(ref cell) => Rc::as_ptr(cell).hash(state),
         }
     }
 }
 
+// Hashes the items of a map or a set, combined in a way that doesn't depend on their order
+fn hash_unordered<T: Hash, I: ExactSizeIterator<Item = T>, H: Hasher>(items: I, state: &mut H) {
+    items.len().hash(state);
+    let mut sum: u64 = 0;
+    for item in items {
+        let mut hasher = DefaultHasher::new();
+        item.hash(&mut hasher);
+        sum = sum.wrapping_add(hasher.finish());
+    }
+    sum.hash(state);
+}
 
 impl RLType {
-    // Returns true if the RLType is atomic, i.e. not a List
-    pub fn is_atom(&self) -> bool {
+    // Returns true if the value can be called: a builtin or a lambda
+    pub fn is_function(&self) -> bool {
+        matches!(*self, RLType::Proc(_) | RLType::Native(_) | RLType::Lambda(_))
+    }
+
+    // Calls this function with already evaluated arguments, lambdas are executed in a new
+    // environment on top of env
+    pub fn call(&self, args: Vec<RLType>, env: &Env, ctx: &mut Context) -> RLResult {
+        apply(self, args, env, ctx)
+    }
+
+    // The name of the type of this value, as shown in error messages
+    pub fn type_name(&self) -> &'static str {
         match *self {
-            RLType::List(_) => false,
-            _ => true,
+            RLType::Nil => "nil",
+            RLType::True | RLType::False => "boolean",
+            RLType::Symbol(_) => "symbol",
+            RLType::Keyword(_) => "keyword",
+            RLType::Str(_) => "string",
+            RLType::Char(_) => "char",
+            RLType::Number(_) => "number",
+            RLType::Proc(_) | RLType::Native(_) => "procedure",
+            RLType::Lambda(_) => "lambda",
+            RLType::List(_) => "list",
+            RLType::Map(_) => "map",
+            RLType::Set(_) => "set",
+            RLType::Atom(_) => "atom",
+            RLType::Vector(_) => "vector",
         }
     }
 }
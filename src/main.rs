@@ -1,29 +1,96 @@
-mod lisp;
+extern crate lisp;
 
+mod editor;
+mod repl;
+
+use std::env;
 use std::io;
-use std::io::Write;
-use lisp::eval::parse_and_eval;
+use std::io::{IsTerminal, Read};
+use std::process;
+use lisp::{Interpreter, IntoLisp, RLResult, RLType};
+use repl::Repl;
+
+const USAGE: &str = "Usage:
+    lisp                      start the REPL, or run the script piped to stdin
+    lisp <script> [args...]   run a script, - reads it from stdin
+    lisp -e <expr> [args...]  evaluate an expression and print its value
+    lisp -h                   show this message
+
+Script arguments are bound to *argv*.";
 
 fn main() {
-    let root_env = lisp::env::Environment::default();
-
-    println!("Lispr interpreter - v 0.1");
-    println!("^C to exit");
-
-    loop {
-        print!(">> ");
-        io::stdout().flush().ok().expect("Cannot flush stdout.");
-
-        let mut input: String = String::new();
-        
-        io::stdin()
-            .read_line(&mut input)
-            .ok()
-            .expect("Error when reading.");
-
-        match parse_and_eval(&input, &root_env) {
-            Ok(value) => println!("{}", value),
-            Err(error) => println!("Error: {:?}", error.get_message())
-        }
+    let mut args = env::args().skip(1);
+    let mut interpreter = Interpreter::new();
+
+    let code = match args.next() {
+        Some(ref flag) if flag == "-h" || flag == "--help" => {
+            println!("{}", USAGE);
+            0
+        },
+        Some(ref flag) if flag == "-e" => {
+            match args.next() {
+                Some(expr) => {
+                    set_argv(&mut interpreter, args);
+                    run(interpreter.eval_str(&expr), true)
+                },
+                None => usage_error("-e requires an expression"),
+            }
+        },
+        Some(ref path) if path == "-" => {
+            set_argv(&mut interpreter, args);
+            run_stdin(&mut interpreter)
+        },
+        Some(ref flag) if flag.starts_with('-') => usage_error(&format!("unknown option {}", flag)),
+        Some(path) => {
+            set_argv(&mut interpreter, args);
+            run(interpreter.load_file(path), false)
+        },
+        None if io::stdin().is_terminal() => {
+            set_argv(&mut interpreter, args);
+            Repl::new(interpreter).run();
+            0
+        },
+        None => {
+            set_argv(&mut interpreter, args);
+            run_stdin(&mut interpreter)
+        },
+    };
+
+    process::exit(code);
+}
+
+// Binds the remaining command line arguments to *argv*
+fn set_argv<I: Iterator<Item = String>>(interpreter: &mut Interpreter, args: I) {
+    interpreter.define("*argv*", args.collect::<Vec<String>>().into_lisp());
+}
+
+// Reports the result of a script, returning the exit code of the process
+fn run(result: RLResult, print_value: bool) -> i32 {
+    match result {
+        Ok(RLType::Nil) => 0,
+        Ok(value) => {
+            if print_value {
+                println!("{:?}", value);
+            }
+            0
+        },
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            1
+        },
     }
 }
+
+fn run_stdin(interpreter: &mut Interpreter) -> i32 {
+    let mut source = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut source) {
+        eprintln!("Error: cannot read stdin: {}", e);
+        return 1;
+    }
+    run(interpreter.eval_str(&source), false)
+}
+
+fn usage_error(message: &str) -> i32 {
+    eprintln!("Error: {}\n\n{}", message, USAGE);
+    2
+}
@@ -0,0 +1,344 @@
+use std::io;
+use std::io::{BufRead, Write};
+use std::time::Instant;
+
+use lisp::{Interpreter, RLResult, RLType, ErrorKind, parse_all};
+use lisp::lisp::printer;
+
+use editor::{Editor, ReadLine};
+use editor::history::History;
+use editor::terminal::RawMode;
+
+const PROMPT: &str = ">> ";
+const CONTINUATION_PROMPT: &str = ".. ";
+
+const HELP: &str = "The last results are bound to *1, *2 and *3, the last error to *e.
+
+Commands:
+    :help          show this message
+    :quit          exit the REPL, as does ^D
+    :env           list the bindings in the root environment
+    :load <file>   evaluate the forms in a file
+    :reset         start over with a new default environment
+    :time <expr>   evaluate an expression and show how long it took
+    :type <expr>   show the type of the value of an expression";
+
+// What the REPL should do after handling a line
+#[derive(Debug, PartialEq)]
+pub enum Flow {
+    Continue,
+    Quit,
+}
+
+// The REPL collects lines until they contain complete forms, then evaluates each form
+// and prints its result. Lines starting with : are commands for the REPL itself.
+pub struct Repl {
+    interpreter: Interpreter,
+    // lines read so far for an incomplete form
+    buffer: String,
+    // the *argv* bound by the host, bound again by :reset
+    argv: Option<RLType>,
+}
+
+impl Repl {
+    pub fn new(interpreter: Interpreter) -> Repl {
+        let argv = interpreter.env().borrow().lookup("*argv*").ok();
+        let mut repl = Repl {
+            interpreter,
+            buffer: String::new(),
+            argv,
+        };
+        repl.clear_results();
+        repl
+    }
+
+    // Binds *1, *2, *3 and *e to nil
+    fn clear_results(&mut self) {
+        for name in &["*1", "*2", "*3", "*e"] {
+            self.interpreter.define(name, RLType::Nil);
+        }
+    }
+
+    // Binds a value to *1, moving the previous ones to *2 and *3, or an error to *e.
+    // Thrown values are bound as they are, other errors as their message.
+    fn remember(&mut self, result: &RLResult) {
+        match *result {
+            Ok(ref value) => {
+                for &(from, to) in &[("*2", "*3"), ("*1", "*2")] {
+                    let previous = self.interpreter.env().borrow().lookup(from)
+                        .unwrap_or(RLType::Nil);
+                    self.interpreter.define(to, previous);
+                }
+                self.interpreter.define("*1", value.clone());
+            },
+            Err(ref error) => {
+                let value = match *error.kind() {
+                    ErrorKind::Thrown(ref value) => value.clone(),
+                    _ => RLType::Str(error.to_string()),
+                };
+                self.interpreter.define("*e", value);
+            },
+        }
+    }
+
+    // The prompt for the next line, different when the previous lines have unbalanced parens
+    pub fn prompt(&self) -> &'static str {
+        if self.buffer.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        }
+    }
+
+    // Adds a line of input. Once the input read so far is complete every form in it is
+    // evaluated and its result written to out, evaluation stops at the first error.
+    pub fn feed<W: Write>(&mut self, line: &str, out: &mut W) -> io::Result<Flow> {
+        if self.buffer.is_empty() && line.trim_start().starts_with(':') {
+            if let Some(flow) = self.command(line.trim(), out)? {
+                return Ok(flow);
+            }
+        }
+
+        self.buffer.push_str(line);
+        if !self.buffer.ends_with('\n') {
+            self.buffer.push('\n');
+        }
+
+        if self.buffer.trim().is_empty() {
+            self.buffer.clear();
+            return Ok(Flow::Continue);
+        }
+
+        let forms = match parse_all(&self.buffer) {
+            Ok(forms) => forms,
+            Err(ref error) if error.is_incomplete_input() => return Ok(Flow::Continue),
+            Err(error) => {
+                self.buffer.clear();
+                writeln!(out, "Error: {}", error)?;
+                return Ok(Flow::Continue);
+            },
+        };
+        self.buffer.clear();
+
+        for form in forms {
+            let result = self.interpreter.eval_value(form);
+            self.remember(&result);
+            if !print_result(result, out)? {
+                break;
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    // Runs a REPL command, returns None if the line is not a known command
+    fn command<W: Write>(&mut self, line: &str, out: &mut W) -> io::Result<Option<Flow>> {
+        let (command, arg) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, ""),
+        };
+
+        match command {
+            ":help" => writeln!(out, "{}", HELP)?,
+            ":quit" => return Ok(Some(Flow::Quit)),
+            ":env" => {
+                let env = self.interpreter.env().borrow();
+                for name in env.names() {
+                    if let Ok(value) = env.lookup(&name) {
+                        writeln!(out, "{} = {:?}", name, value)?;
+                    }
+                }
+            },
+            ":load" if arg.is_empty() => writeln!(out, "Usage: :load <file>")?,
+            ":load" => {
+                let result = self.interpreter.load_file(arg);
+                print_result(result, out)?;
+            },
+            ":reset" => {
+                self.interpreter.reset();
+                if let Some(ref argv) = self.argv {
+                    self.interpreter.define("*argv*", argv.clone());
+                }
+                self.clear_results();
+                writeln!(out, "Environment reset")?;
+            },
+            ":time" => {
+                let start = Instant::now();
+                let result = self.interpreter.eval_str(arg);
+                let elapsed = start.elapsed();
+                print_result(result, out)?;
+                writeln!(out, "Elapsed: {:.3} ms", elapsed.as_secs_f64() * 1000.0)?;
+            },
+            ":type" => {
+                match self.interpreter.eval_str(arg) {
+                    Ok(value) => writeln!(out, "{}", value.type_name())?,
+                    Err(error) => writeln!(out, "Error: {}", error)?,
+                }
+            },
+            _ => return Ok(None),
+        }
+        Ok(Some(Flow::Continue))
+    }
+
+    // Reads lines from input until the end of the input or :quit
+    pub fn run_with<R: BufRead, W: Write>(&mut self, mut input: R, out: &mut W) -> io::Result<()> {
+        loop {
+            write!(out, "{}", self.prompt())?;
+            out.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                // end of input, an incomplete form is dropped
+                self.buffer.clear();
+                return writeln!(out);
+            }
+
+            if self.feed(&line, out)? == Flow::Quit {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn run(&mut self) {
+        println!("Lispr interpreter - v 0.1");
+        println!("Type :help for the list of commands, :quit or ^D to exit");
+
+        // without a terminal that supports raw mode, lines are read without editing
+        if RawMode::enable().is_err() {
+            let stdin = io::stdin();
+            self.run_with(stdin.lock(), &mut io::stdout()).expect("Error when reading.");
+            return;
+        }
+
+        let mut editor = Editor::new(History::from_home());
+        let mut out = io::stdout();
+        loop {
+            let names = self.interpreter.env().borrow().names();
+            let line = match editor.read_line(self.prompt(), &names).expect("Error when reading.") {
+                ReadLine::Line(line) => line,
+                ReadLine::Interrupted => {
+                    // ^C drops the form being read
+                    self.buffer.clear();
+                    continue;
+                },
+                ReadLine::Eof => return,
+            };
+
+            if self.feed(&line, &mut out).expect("Error when writing.") == Flow::Quit {
+                return;
+            }
+        }
+    }
+}
+
+// Prints the readable form of a value or an error, returns true for values
+fn print_result<W: Write>(result: RLResult, out: &mut W) -> io::Result<bool> {
+    match result {
+        Ok(value) => {
+            let text = printer::pretty(&value, printer::DEFAULT_WIDTH, true);
+            writeln!(out, "{}", text).map(|_| true)
+        },
+        Err(error) => writeln!(out, "Error: {}", error).map(|_| false),
+    }
+}
+
+#[cfg(test)]
+fn feed_lines(repl: &mut Repl, lines: &[&str]) -> String {
+    let mut out = Vec::new();
+    for line in lines {
+        repl.feed(line, &mut out).unwrap();
+    }
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn repl_reads_forms_over_multiple_lines() {
+    let mut repl = Repl::new(Interpreter::new());
+
+    assert_eq!(feed_lines(&mut repl, &["(def! max (lambda (a b)", "  (if (> a b)"]), "");
+    assert_eq!(repl.prompt(), CONTINUATION_PROMPT);
+
+    assert_eq!(feed_lines(&mut repl, &["    a b)))", "(max 21 42)"]), "nil\n42\n");
+    assert_eq!(repl.prompt(), PROMPT);
+
+    // strings can span lines too
+    assert_eq!(feed_lines(&mut repl, &["\"a", "b\""]), "\"a\\nb\"\n");
+}
+
+#[test]
+fn repl_evaluates_every_form_on_a_line() {
+    let mut repl = Repl::new(Interpreter::new());
+
+    assert_eq!(feed_lines(&mut repl, &["(def! x 2) (* x 21) x"]), "nil\n42\n2\n");
+    assert_eq!(feed_lines(&mut repl, &["(+ x 1) (+ x y) (def! z 1)"]),
+               "3\nError: No value for given key: y\n");
+    assert_eq!(feed_lines(&mut repl, &["z"]), "Error: No value for given key: z\n");
+}
+
+#[test]
+fn repl_reports_parse_errors() {
+    let mut repl = Repl::new(Interpreter::new());
+
+    assert_eq!(feed_lines(&mut repl, &["(+ 1 2))"]),
+               "Error: Parse error at line 1, column 8: unbalanced parentheses\n");
+    assert_eq!(repl.prompt(), PROMPT);
+    assert_eq!(feed_lines(&mut repl, &["", "   "]), "");
+}
+
+#[test]
+fn repl_exits_on_end_of_input() {
+    let mut repl = Repl::new(Interpreter::new());
+    let mut out = Vec::new();
+
+    repl.run_with(io::Cursor::new("(+ 1 2)\n(+ 3"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), ">> 3\n>> .. \n");
+
+    let mut out = Vec::new();
+    repl.run_with(io::Cursor::new(":quit\n(+ 1 2)\n"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), ">> ");
+}
+
+#[test]
+fn repl_runs_commands() {
+    let mut repl = Repl::new(Interpreter::new());
+
+    assert!(feed_lines(&mut repl, &[":help"]).contains("Commands:"));
+    assert_eq!(feed_lines(&mut repl, &[":type (+ 1 2)"]), "number\n");
+    assert_eq!(feed_lines(&mut repl, &[":type \"a\""]), "string\n");
+    assert!(feed_lines(&mut repl, &[":time (* 6 7)"]).starts_with("42\nElapsed: "));
+
+    feed_lines(&mut repl, &["(def! answer 42)"]);
+    let env = feed_lines(&mut repl, &[":env"]);
+    assert!(env.contains("answer = 42\n"));
+    assert!(env.contains("+ = #<procedure>\n"));
+
+    assert_eq!(feed_lines(&mut repl, &[":reset", "answer"]),
+               "Environment reset\nError: No value for given key: answer\n");
+    assert_eq!(feed_lines(&mut repl, &[":load"]), "Usage: :load <file>\n");
+    assert!(feed_lines(&mut repl, &[":load /does/not/exist"]).starts_with("Error: Cannot read"));
+    assert_eq!(repl.feed(":quit", &mut Vec::new()).unwrap(), Flow::Quit);
+}
+
+#[test]
+fn repl_keeps_argv_on_reset() {
+    let mut interpreter = Interpreter::new();
+    interpreter.define("*argv*", RLType::List(vec![RLType::Str("a".to_string())]));
+    let mut repl = Repl::new(interpreter);
+
+    feed_lines(&mut repl, &["(def! *argv* 1)"]);
+    assert_eq!(feed_lines(&mut repl, &[":reset", "*argv*"]), "Environment reset
+(\"a\")\n");
+}
+
+#[test]
+fn repl_binds_recent_results() {
+    let mut repl = Repl::new(Interpreter::new());
+
+    assert_eq!(feed_lines(&mut repl, &["*1 *e"]), "nil\nnil\n");
+    assert_eq!(feed_lines(&mut repl, &["1 2 3", "(list *1 *2 *3)"]), "1\n2\n3\n(3 2 1)\n");
+    assert_eq!(feed_lines(&mut repl, &["*1"]), "(3 2 1)\n");
+
+    feed_lines(&mut repl, &["(+ 1 undefined)"]);
+    assert_eq!(feed_lines(&mut repl, &["*e"]), "\"No value for given key: undefined\"\n");
+    feed_lines(&mut repl, &["(throw 42)"]);
+    assert_eq!(feed_lines(&mut repl, &["*e"]), "42\n");
+}